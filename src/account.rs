@@ -1,16 +1,27 @@
-use crate::{success, try_or_throw, utils, warning};
+use crate::{begin, cas, end, error, success, try_or_throw, utils, vault, warning};
 use anyhow::anyhow;
 use anyhow::Result;
+use dialoguer::{theme::ColorfulTheme, Password};
 use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct AccountData {
     pub stuid: String,
     pub password: String,
 }
+
+/// 调试输出中隐去密码
+impl std::fmt::Debug for AccountData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountData")
+            .field("stuid", &self.stuid)
+            .field("password", &"***")
+            .finish()
+    }
+}
 pub type Accounts = HashMap<String, AccountData>;
 pub struct Account {
     pub accounts: Accounts,
@@ -29,8 +40,7 @@ impl Account {
                 return Err(anyhow!("输入账号"));
             };
             accounts.insert(user.clone(), new_account.clone());
-            let json = try_or_throw!(serde_json::to_string(&accounts), "序列化账号");
-            try_or_throw!(fs::write(&path_accounts, json), "写入账号");
+            try_or_throw!(Self::store_accounts(&path_accounts, &accounts), "写入账号");
             try_or_throw!(settings.set_default_user(&user), "设置默认用户");
             new_account
         } else {
@@ -48,37 +58,127 @@ impl Account {
     }
 
     /// 获取所有的已有账号
+    ///
+    /// 账号文件用主密码加密；旧版本的明文账号文件会在读取后加密保存
     fn load_accounts(path_accounts: &PathBuf) -> Result<Accounts> {
         let data = try_or_throw!(fs::read_to_string(path_accounts), "读取账号");
-        let accounts: Accounts = try_or_throw!(serde_json::from_str(&data), "解析账号");
+        if !vault::is_sealed(&data) {
+            let accounts: Accounts = try_or_throw!(serde_json::from_str(&data), "解析账号");
+            if !accounts.is_empty() {
+                warning!("账号文件未加密 => 设置主密码以加密账号文件");
+                try_or_throw!(Self::store_accounts(path_accounts, &accounts), "加密账号");
+                success!("已加密账号文件 -> {}", path_accounts.display());
+            }
+            return Ok(accounts);
+        }
 
-        #[cfg(debug_assertions)]
-        success!("已读取账号：{:?}", accounts);
+        let mut attempt = 1;
+        let plaintext = loop {
+            let passphrase = vault::passphrase(false)?;
+            match vault::open(&data, &passphrase) {
+                Ok(plaintext) => break plaintext,
+                Err(e) => {
+                    vault::forget_passphrase();
+                    if attempt == utils::MAX_RETRIES || std::env::var(vault::PASSPHRASE_ENV).is_ok()
+                    {
+                        return Err(e);
+                    }
+                    error!("{e}");
+                    attempt += 1;
+                }
+            }
+        };
+        let accounts: Accounts = try_or_throw!(serde_json::from_slice(&plaintext), "解析账号");
         Ok(accounts)
     }
 
+    /// 用主密码加密并写入账号文件
+    fn store_accounts(path_accounts: &PathBuf, accounts: &Accounts) -> Result<()> {
+        let json = serde_json::to_string(accounts)?;
+        let is_new = !vault::is_sealed(&fs::read_to_string(path_accounts).unwrap_or_default());
+        let passphrase = vault::passphrase(is_new)?;
+        utils::atomic_write_private(path_accounts, vault::seal(json.as_bytes(), &passphrase)?)
+    }
+
     /// 通过输入获得一个 AccountData 和对应用户名 String
+    ///
+    /// 密码不回显并需要输入两次，保存前会试登录以检查账号是否可用
     fn read_in_account() -> Result<(AccountData, String)> {
         let mut stuid = String::new();
-        let mut password = String::new();
-        let mut user = String::new();
         println!("账号：(用户名，学号，密码)");
-        print!("请输入用户名：");
-        try_or_throw!(io::stdout().flush(), "刷新 stdout");
-        try_or_throw!(io::stdin().read_line(&mut user), "读取用户名");
-        let user = user.trim().to_string();
+        let user = loop {
+            let mut user = String::new();
+            print!("请输入用户名：");
+            try_or_throw!(io::stdout().flush(), "刷新 stdout");
+            if try_or_throw!(io::stdin().read_line(&mut user), "读取用户名") == 0 {
+                return Err(anyhow!("读取用户名：输入已结束"));
+            }
+            let user = user.trim().to_string();
+            match utils::check_user_name(&user) {
+                Ok(()) => break user,
+                Err(e) => error!("{e}"),
+            }
+        };
 
         print!("请输入学号：");
         try_or_throw!(io::stdout().flush(), "刷新 stdout");
         try_or_throw!(io::stdin().read_line(&mut stuid), "读取学号");
         let stuid = stuid.trim().to_string();
 
-        print!("请输入密码：");
-        try_or_throw!(io::stdout().flush(), "刷新 stdout");
-        try_or_throw!(io::stdin().read_line(&mut password), "读取密码");
-        let password = password.trim().to_string();
+        let account = Self::read_in_password(stuid)?;
+        Ok((account, user))
+    }
+
+    /// 读入学号 stuid 对应的密码，并试登录直到账号可用或用户放弃
+    fn read_in_password(stuid: String) -> Result<AccountData> {
+        loop {
+            let password = try_or_throw!(
+                Password::with_theme(&ColorfulTheme::default())
+                    .with_prompt("请输入密码")
+                    .with_confirmation("请确认密码", "两次输入的密码不一致")
+                    .interact(),
+                "读取密码"
+            );
+            let account = AccountData {
+                stuid: stuid.clone(),
+                password,
+            };
+
+            begin!("验证账号");
+            let Err(e) = cas::verify_account(&account) else {
+                end!("验证账号");
+                return Ok(account);
+            };
+            println!();
+            error!("验证账号：{e}");
+            if cas::is_bad_credentials(&e) {
+                if !confirm("是否重新输入密码？(y/n)")? {
+                    return Err(anyhow!("学号或密码错误"));
+                }
+            } else if confirm("无法验证账号，是否仍然保存？(y/n)")? {
+                return Ok(account);
+            } else {
+                return Err(e);
+            }
+        }
+    }
 
-        Ok((AccountData { stuid, password }, user))
+    /// 更新默认账号的密码，所有使用同一学号的账号一并更新
+    pub fn update_password(&mut self) -> Result<()> {
+        println!("更新学号 {} 的密码", self.default.stuid);
+        let updated = Self::read_in_password(self.default.stuid.clone())?;
+        for account in self.accounts.values_mut() {
+            if account.stuid == updated.stuid {
+                account.password = updated.password.clone();
+            }
+        }
+        self.default = updated;
+        try_or_throw!(
+            Self::store_accounts(&self.path_accounts, &self.accounts),
+            "写入账号"
+        );
+        success!("已更新密码 -> {}", self.path_accounts.display());
+        Ok(())
     }
 
     /// 添加一个账号，并将此用户修改为默认用户
@@ -88,8 +188,10 @@ impl Account {
         };
         self.accounts.insert(user.clone(), new_account.clone());
 
-        let json = try_or_throw!(serde_json::to_string(&self.accounts), "序列化账号");
-        try_or_throw!(fs::write(&self.path_accounts, json), "写入账号");
+        try_or_throw!(
+            Self::store_accounts(&self.path_accounts, &self.accounts),
+            "写入账号"
+        );
         self.default = new_account;
         try_or_throw!(settings.set_default_user(&user), "设置默认用户");
 
@@ -100,9 +202,15 @@ impl Account {
     /// 该函数保证至少有一个默认账号
     pub fn remove_account(&mut self, settings: &mut utils::Settings, user: &str) -> Result<bool> {
         self.accounts.remove(user);
-        let json = try_or_throw!(serde_json::to_string(&self.accounts), "序列化账号");
-        try_or_throw!(fs::write(&self.path_accounts, json), "写入账号");
+        try_or_throw!(
+            Self::store_accounts(&self.path_accounts, &self.accounts),
+            "写入账号"
+        );
         success!("删除用户 {user} -> {}", self.path_accounts.display());
+        // 账号已从列表中删除，数据目录删除失败时仍继续切换默认账号
+        if let Err(e) = utils::Config::remove_account_data(user) {
+            error!("删除账号数据：{e}");
+        }
 
         if settings.user != user {
             return Ok(false);
@@ -122,3 +230,12 @@ impl Account {
         Ok(true)
     }
 }
+
+/// 询问 y/n，返回是否为 y
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim() == "y")
+}
@@ -0,0 +1,347 @@
+use crate::account;
+
+use anyhow::{anyhow, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::LOCATION;
+use reqwest::Url;
+use serde_json::Value;
+use std::time::Duration;
+
+const LOGIN_URL: &str = "https://zjuam.zju.edu.cn/cas/login";
+const PUBKEY_URL: &str = "https://zjuam.zju.edu.cn/cas/v2/getPubKey";
+const CAS_HOST: &str = "zjuam.zju.edu.cn";
+pub const BROWSER_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:88.0) Gecko/20100101 Firefox/88.0";
+
+/// 接入统一身份认证的服务
+///
+/// 新的后端(如教务、图书馆)只需登记自己的服务地址，登录时会一并建立会话
+pub struct Service {
+    /// 服务名，用于提示
+    pub name: &'static str,
+    /// 服务地址，未登录时会被重定向到统一身份认证
+    pub url: &'static str,
+}
+
+/// 发送请求的方式，便于调用方套上限流与重试
+pub type Send<'a> = dyn Fn(RequestBuilder) -> reqwest::Result<Response> + Sync + 'a;
+
+/// 统一身份认证客户端
+///
+/// cookie 保存在 client 中；ticket_client 须与 client 共享 cookie 且不跟随重定向，
+/// 用于从重定向中取出服务票据
+pub struct CasClient<'a> {
+    client: &'a Client,
+    ticket_client: &'a Client,
+    send: &'a Send<'a>,
+}
+
+impl<'a> CasClient<'a> {
+    pub fn new(client: &'a Client, ticket_client: &'a Client, send: &'a Send<'a>) -> Self {
+        CasClient {
+            client,
+            ticket_client,
+            send,
+        }
+    }
+
+    /// 向统一身份认证提交一次登录
+    ///
+    /// parallel 为 false 时依次请求登录页与公钥，不占用 rayon 线程池，
+    /// 供持有锁的重新登录使用，避免工作窃取造成死锁
+    pub fn login(
+        &self,
+        account: &account::AccountData,
+        parallel: bool,
+    ) -> std::result::Result<(), LoginError> {
+        let (execution, pubkey) = if parallel {
+            rayon::join(|| self.fetch_execution(), || self.fetch_pubkey())
+        } else {
+            (self.fetch_execution(), self.fetch_pubkey())
+        };
+        let execution = execution?;
+        let (modulus, exponent) = pubkey?;
+
+        let rsapwd = rsa_no_padding(&account.password, &modulus, &exponent);
+
+        let params = [
+            ("username", account.stuid.as_str()),
+            ("password", &rsapwd),
+            ("execution", &execution),
+            ("_eventId", "submit"),
+            ("authcode", ""),
+            ("rememberMe", "true"),
+        ];
+        let res = (self.send)(self.client.post(LOGIN_URL).form(&params))?;
+
+        #[cfg(debug_assertions)]
+        println!("{:?}", res);
+
+        if res.status().is_server_error() {
+            return Err(LoginError::ServiceUnavailable(res.status().to_string()));
+        }
+        if !res.url().to_string().contains(LOGIN_URL) {
+            return Ok(());
+        }
+
+        // 仍停留在登录页，说明登录失败
+        Err(LoginError::from_login_page(&res.text()?))
+    }
+
+    /// 访问服务，建立服务一侧的会话
+    ///
+    /// 已登录统一身份认证时，重定向过程中会自动完成票据校验
+    pub fn authorize(&self, service: &Service) -> reqwest::Result<Response> {
+        (self.send)(self.client.get(service.url))
+    }
+
+    /// 服务一侧的会话是否仍然有效
+    pub fn is_authorized(&self, service: &Service) -> reqwest::Result<bool> {
+        Ok(!is_login_page(self.authorize(service)?.url()))
+    }
+
+    /// 为任意服务地址申请服务票据(ST-...)，需要已经登录
+    pub fn service_ticket(&self, service_url: &str) -> std::result::Result<String, LoginError> {
+        let url = Url::parse_with_params(LOGIN_URL, &[("service", service_url)])
+            .map_err(|e| LoginError::Other(e.into()))?;
+        let res = (self.send)(self.ticket_client.get(url))?;
+        if res.status().is_server_error() {
+            return Err(LoginError::ServiceUnavailable(res.status().to_string()));
+        }
+        res.headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| Url::parse(location).ok())
+            .and_then(|location| {
+                location
+                    .query_pairs()
+                    .find(|(key, _)| key == "ticket")
+                    .map(|(_, ticket)| ticket.into_owned())
+            })
+            .ok_or(LoginError::Other(anyhow!(
+                "未取得 {service_url} 的服务票据，可能尚未登录"
+            )))
+    }
+
+    /// 抓取登录页中的 execution 字段
+    fn fetch_execution(&self) -> std::result::Result<String, LoginError> {
+        let res = (self.send)(self.client.get(LOGIN_URL))?;
+        if res.status().is_server_error() {
+            return Err(LoginError::ServiceUnavailable(res.status().to_string()));
+        }
+        let text = res.text()?;
+        let re =
+            regex::Regex::new(r#"<input type="hidden" name="execution" value="(.*?)" />"#).unwrap();
+        re.captures(&text)
+            .and_then(|cap| cap.get(1).map(|m| m.as_str().to_string()))
+            .ok_or(LoginError::Other(anyhow!("Execution value not found")))
+    }
+
+    /// 获取加密密码用的 RSA 公钥(modulus, exponent)
+    fn fetch_pubkey(&self) -> std::result::Result<(String, String), LoginError> {
+        let json: Value = (self.send)(self.client.get(PUBKEY_URL))?.json()?;
+        let (Some(modulus), Some(exponent)) = (json["modulus"].as_str(), json["exponent"].as_str())
+        else {
+            return Err(LoginError::Other(anyhow!("Modulus or exponent not found")));
+        };
+        Ok((modulus.to_string(), exponent.to_string()))
+    }
+}
+
+/// 是否为统一身份认证的登录页
+pub fn is_login_page(url: &Url) -> bool {
+    url.host_str() == Some(CAS_HOST) && url.path().starts_with("/cas/login")
+}
+
+/// 用一个临时的会话试登录，检查账号是否可用，不影响当前会话的 cookie
+pub fn verify_account(account: &account::AccountData) -> Result<()> {
+    let client = Client::builder()
+        .cookie_store(true)
+        .user_agent(BROWSER_USER_AGENT)
+        .timeout(Duration::from_secs(30))
+        .no_proxy()
+        .build()?;
+    let send = |request: RequestBuilder| request.send();
+    CasClient::new(&client, &client, &send).login(account, true)?;
+    Ok(())
+}
+
+fn rsa_no_padding(src: &str, modulus: &str, exponent: &str) -> String {
+    let m = num::BigUint::parse_bytes(modulus.as_bytes(), 16).unwrap();
+    let e = num::BigUint::parse_bytes(exponent.as_bytes(), 16).unwrap();
+
+    let input_nr = num::BigUint::from_bytes_be(src.as_bytes());
+
+    let crypt_nr = input_nr.modpow(&e, &m);
+
+    crypt_nr
+        .to_bytes_be()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// 登录失败的原因
+#[derive(Debug)]
+pub enum LoginError {
+    /// 学号或密码错误
+    BadCredentials,
+    /// 账号被锁定
+    Locked(String),
+    /// 需要输入验证码，通常是失败次数过多
+    CaptchaRequired,
+    /// 统一身份认证暂时不可用
+    ServiceUnavailable(String),
+    /// 统一身份认证给出的其他拒绝原因
+    Rejected(String),
+    /// 网络等其他原因
+    Other(anyhow::Error),
+}
+
+impl LoginError {
+    /// 是否为暂时性的失败，只有暂时性的失败才值得重试
+    ///
+    /// 密码错误时重试只会加快账号被锁定
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            LoginError::ServiceUnavailable(_) | LoginError::Other(_)
+        )
+    }
+
+    /// 从登录失败后返回的登录页中找出错误提示并判断失败原因
+    ///
+    /// 登录表单本身总带有验证码字段，只能依据错误提示判断，没有提示时视为密码错误
+    fn from_login_page(text: &str) -> Self {
+        let re = regex::Regex::new(r#"id="(?:msg|errormsg)"[^>]*>\s*([^<]*?)\s*<"#).unwrap();
+        match re.captures(text).and_then(|cap| cap.get(1)) {
+            Some(message) if !message.as_str().is_empty() => {
+                LoginError::from_cas_message(message.as_str())
+            }
+            _ => LoginError::BadCredentials,
+        }
+    }
+
+    /// 根据登录页上的错误提示判断失败原因
+    fn from_cas_message(message: &str) -> Self {
+        if message.contains("锁定") || message.contains("冻结") {
+            LoginError::Locked(message.to_string())
+        } else if message.contains("验证码") {
+            LoginError::CaptchaRequired
+        } else if message.contains("密码") || message.contains("用户名") || message.contains("账号")
+        {
+            LoginError::BadCredentials
+        } else if message.contains("繁忙") || message.contains("维护") || message.contains("稍后")
+        {
+            LoginError::ServiceUnavailable(message.to_string())
+        } else {
+            LoginError::Rejected(message.to_string())
+        }
+    }
+}
+
+impl std::fmt::Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoginError::BadCredentials => write!(f, "学号或密码错误"),
+            LoginError::Locked(message) => write!(f, "账号已被锁定：{message}"),
+            LoginError::CaptchaRequired => {
+                write!(f, "需要验证码，请先在浏览器中登录一次统一身份认证")
+            }
+            LoginError::ServiceUnavailable(message) => {
+                write!(f, "统一身份认证暂时不可用：{message}")
+            }
+            LoginError::Rejected(message) => write!(f, "统一身份认证拒绝登录：{message}"),
+            LoginError::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+impl From<reqwest::Error> for LoginError {
+    fn from(e: reqwest::Error) -> Self {
+        LoginError::Other(e.into())
+    }
+}
+
+/// 登录失败是否因为学号或密码错误
+pub fn is_bad_credentials(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref(), Some(LoginError::BadCredentials))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 登录失败后返回的登录页，表单中总带有验证码字段
+    fn login_page(message: &str) -> String {
+        format!(
+            r#"<form id="fm1" action="/cas/login" method="post">
+<input id="authcode" name="authcode" type="text" />
+<img id="captcha" src="/cas/code" />
+<p id="errormsg" class="login-error-msg">{message}</p>
+</form>"#
+        )
+    }
+
+    #[test]
+    fn bad_credentials() {
+        let e = LoginError::from_cas_message("用户名或密码错误");
+        assert!(matches!(e, LoginError::BadCredentials));
+        assert!(!e.is_transient());
+    }
+
+    #[test]
+    fn locked() {
+        let e = LoginError::from_cas_message("账号已被锁定，请10分钟后再试");
+        assert!(matches!(e, LoginError::Locked(_)));
+        assert!(!e.is_transient());
+        let e = LoginError::from_cas_message("该账号已被冻结，请联系管理员");
+        assert!(matches!(e, LoginError::Locked(_)));
+    }
+
+    #[test]
+    fn captcha_required() {
+        let e = LoginError::from_cas_message("请输入验证码");
+        assert!(matches!(e, LoginError::CaptchaRequired));
+        assert!(!e.is_transient());
+        let e = LoginError::from_cas_message("验证码错误");
+        assert!(matches!(e, LoginError::CaptchaRequired));
+    }
+
+    #[test]
+    fn service_unavailable() {
+        let e = LoginError::from_cas_message("系统繁忙，请稍后再试");
+        assert!(matches!(e, LoginError::ServiceUnavailable(_)));
+        assert!(e.is_transient());
+    }
+
+    #[test]
+    fn rejected() {
+        let e = LoginError::from_cas_message("当前IP不允许登录");
+        assert!(matches!(e, LoginError::Rejected(_)));
+        assert!(!e.is_transient());
+    }
+
+    #[test]
+    fn other() {
+        assert!(LoginError::Other(anyhow!("connection reset")).is_transient());
+    }
+
+    #[test]
+    fn login_page_is_judged_by_its_error_message() {
+        // 表单中的 authcode 与 captcha 不代表要求验证码
+        let e = LoginError::from_login_page(&login_page("用户名或密码错误"));
+        assert!(matches!(e, LoginError::BadCredentials));
+        let e = LoginError::from_login_page(&login_page("请输入验证码"));
+        assert!(matches!(e, LoginError::CaptchaRequired));
+        let e = LoginError::from_login_page(&login_page(""));
+        assert!(matches!(e, LoginError::BadCredentials));
+        let page = r#"<span id="msg" class="errors">账号已被锁定</span>"#;
+        assert!(matches!(
+            LoginError::from_login_page(page),
+            LoginError::Locked(_)
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+/// 迷你折线图的八级方块，由低到高
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// 横条末尾不足一格的部分，以 1/8 格为单位
+const EIGHTHS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// 分数段的名称与下限，由高到低
+pub const SCORE_BANDS: [(&str, f64); 9] = [
+    ("95-100", 95.0),
+    ("90-94", 90.0),
+    ("85-89", 85.0),
+    ("80-84", 80.0),
+    ("75-79", 75.0),
+    ("70-74", 70.0),
+    ("65-69", 65.0),
+    ("60-64", 60.0),
+    ("<60", 0.0),
+];
+
+/// 分数所在分数段的序号
+pub fn score_band(score: f64) -> usize {
+    SCORE_BANDS
+        .iter()
+        .position(|&(_, min)| score >= min)
+        .unwrap_or(SCORE_BANDS.len() - 1)
+}
+
+/// 迷你折线图，每个值一个字符，按 [min, max] 映射到八级方块
+///
+/// 所有值相同时都画成中间的高度
+pub fn sparkline(values: &[f64], min: f64, max: f64) -> String {
+    values
+        .iter()
+        .map(|&value| {
+            if max <= min {
+                return SPARKS[SPARKS.len() / 2];
+            }
+            let ratio = ((value - min) / (max - min)).clamp(0.0, 1.0);
+            SPARKS[(ratio * (SPARKS.len() - 1) as f64).round() as usize]
+        })
+        .collect()
+}
+
+/// 长度与 value / max 成正比的横条，满值时为 width 格，精确到 1/8 格
+///
+/// 返回的横条已用空格补齐到 width 格，便于在其后对齐数值
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    let eighths = if max > 0.0 {
+        ((value / max).clamp(0.0, 1.0) * (width * 8) as f64).round() as usize
+    } else {
+        0
+    };
+    let full = eighths / 8;
+    let mut bar = "█".repeat(full);
+    bar.push_str(EIGHTHS[eighths % 8]);
+    let drawn = full + usize::from(eighths % 8 != 0);
+    bar.push_str(&" ".repeat(width - drawn));
+    bar
+}
@@ -1,52 +1,18 @@
 use crate::{
-    account, begin, command_share, completer, end, error, network, process, try_or_exit, utils,
-    warning,
+    account, command_share, completer, error, network, success, try_or_exit, utils, warning,
 };
-use std::path::PathBuf;
 
 #[cfg(debug_assertions)]
-use crate::success;
+use crate::process;
+use std::path::PathBuf;
 
 /// 保证配置定位、配置文件(必须有存储目录)正确!
 ///
-/// 返回值：
-/// ```txt
-/// (
-///    (
-///         path_accounts,
-///         path_courses,
-///         path_selected_courses,
-///         path_activity_upload_record,
-///         path_cookies,
-///         path_active_courses,
-///     ),
-///     settings,
-/// )
-/// ```
-pub fn config_up() -> (
-    (
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-    ),
-    utils::Settings,
-) {
+/// 返回值：(path_accounts, settings)
+pub fn config_up() -> (PathBuf, utils::Settings) {
     #[cfg(debug_assertions)]
     process!("SETUP");
-    let (
-        path_accounts,
-        path_settings,
-        path_courses,
-        path_selected_courses,
-        path_activity_upload_record,
-        path_cookies,
-        path_active_courses,
-        path_active_semesters,
-    ) = try_or_exit!(utils::Config::init(), "初始化配置文件");
+    let (path_accounts, path_settings) = try_or_exit!(utils::Config::init(), "初始化配置文件");
 
     let mut settings = try_or_exit!(utils::Settings::load(path_settings), "读取配置文件");
 
@@ -63,18 +29,7 @@ pub fn config_up() -> (
     #[cfg(debug_assertions)]
     success!("SETUP");
 
-    (
-        (
-            path_accounts,
-            path_courses,
-            path_selected_courses,
-            path_activity_upload_record,
-            path_cookies,
-            path_active_courses,
-            path_active_semesters,
-        ),
-        settings,
-    )
+    (path_accounts, settings)
 }
 
 /// 保证至少有一个默认账号！
@@ -93,28 +48,12 @@ pub fn account_up(path_accounts: PathBuf, settings: &mut utils::Settings) -> acc
     account
 }
 
-pub fn session_up(
-    path_cookies: PathBuf,
-    path_courses: PathBuf,
-    path_active_courses: PathBuf,
-    path_selected_courses: PathBuf,
-    path_activity_upload_record: PathBuf,
-    path_active_semesters: PathBuf,
-) -> network::Session {
+pub fn session_up(user: &str) -> network::Session {
     #[cfg(debug_assertions)]
     process!("SESSIONUP");
 
-    let session = try_or_exit!(
-        network::Session::try_new(
-            path_cookies,
-            path_courses,
-            path_active_courses,
-            path_selected_courses,
-            path_activity_upload_record,
-            path_active_semesters
-        ),
-        "建立会话"
-    );
+    let paths = try_or_exit!(utils::Config::account_init(user), "初始化账号数据");
+    let session = try_or_exit!(network::Session::try_new(paths), "建立会话");
 
     #[cfg(debug_assertions)]
     success!("SESSIONUP");
@@ -146,42 +85,22 @@ pub fn course_up(session: &network::Session, default_account: &account::AccountD
 /// 2. 至少有一个默认账号
 /// 3. 有课程列表和活跃课程列表
 pub fn all_up() -> (utils::Settings, account::Account, network::Session) {
-    let (
-        (
-            path_accounts,
-            path_courses,
-            path_selected_courses,
-            path_activity_upload_record,
-            path_cookies,
-            path_active_courses,
-            path_active_semesters,
-        ),
-        mut settings,
-    ) = config_up();
+    let (path_accounts, mut settings) = config_up();
     let account = account_up(path_accounts, &mut settings);
-    let session = session_up(
-        path_cookies,
-        path_courses,
-        path_active_courses,
-        path_selected_courses,
-        path_activity_upload_record,
-        path_active_semesters,
-    );
+    let session = session_up(&settings.user);
+    session.apply_settings(&settings);
     course_up(&session, &account.default);
     (settings, account, session)
 }
 
 impl network::Session {
-    /// 切换默认账号后，重新登陆并刷新 学期->课程 映射表
-    pub fn change_default_account(&self, default_account: &account::AccountData) {
-        process!("更换用户 => 更新 学期->课程 映射表与已选课程");
-
-        begin!("重新登录");
-        try_or_exit!(self.relogin(default_account), "重新登录");
-        end!("重新登录");
-
-        try_or_exit!(command_share::upgrade_core(self), "UPGRADE");
-
-        try_or_exit!(self.store_selected_courses(&Vec::new()), "清空已选课程");
+    /// 切换默认账号：载入该账号自己的 cookie、课程与下载记录
+    ///
+    /// 只有该账号还没有 学期->课程 映射表时才需要登录并刷新
+    pub fn change_default_account(&self, user: &str, default_account: &account::AccountData) {
+        let paths = try_or_exit!(utils::Config::account_init(user), "初始化账号数据");
+        try_or_exit!(self.switch_account(paths, default_account), "切换账号");
+        course_up(self, default_account);
+        success!("已切换到用户 {user}");
     }
 }
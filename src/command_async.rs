@@ -1,34 +1,93 @@
-use crate::{
-    account, command_share, error, network, process, success, try_or_exit, try_or_throw, utils,
-    warning,
-};
+use crate::{account, command_share, network, process, success, try_or_throw, utils, warning};
 
 use anyhow::Result;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
+/// 后台线程向终端输出消息的方式，交互模式下不应打乱正在输入的命令行
+pub type Printer = Arc<Mutex<dyn FnMut(String) + Send>>;
+
+pub struct PreLoginHandle {
+    pub thread: JoinHandle<Result<()>>,
+    /// 预登录失败、在前台重新登录后，仍用它提示未读公告
+    pub printer: Printer,
+}
+
 // 交互模式专用的预登录操作，希望减少用户等待登录时间
+//
+// 登录成功后在另一个线程统计未读公告，不阻塞第一条命令
+//
+// 登录失败时返回错误，由主线程在前台处理(如询问是否更新密码)
 pub fn pre_login(
     default_account: account::AccountData,
     session: network::Session,
-) -> JoinHandle<()> {
-    thread::spawn(move || {
+    printer: Printer,
+) -> PreLoginHandle {
+    let thread_printer = printer.clone();
+    let thread = thread::spawn(move || {
         #[cfg(debug_assertions)]
         process!("PRE_LOGIN");
-        try_or_exit!(session.login(&default_account), "登录");
-    })
+        session.login(&default_account)?;
+        notify_unread_announcements(session, thread_printer);
+        Ok(())
+    });
+    PreLoginHandle { thread, printer }
+}
+
+/// 在后台统计各课程的未读公告数，统计完成后提示用户
+///
+/// 所有输出都经由 printer，不直接写终端
+pub fn notify_unread_announcements(session: network::Session, printer: Printer) {
+    thread::spawn(move || {
+        let Ok((unread_counts, failed_num)) = session.count_unread_announcements() else {
+            return;
+        };
+        let mut messages = Vec::new();
+        if !unread_counts.is_empty() {
+            let total: usize = unread_counts.iter().map(|(_, count)| count).sum();
+            let detail: Vec<String> = unread_counts
+                .iter()
+                .map(|(course_name, count)| format!("{course_name} {count}"))
+                .collect();
+            messages.push(format!(
+                "\x1b[90m有 {total} 条未读公告（{}），可使用 announce 查看~\x1b[0m\n",
+                detail.join("，")
+            ));
+        }
+        if failed_num > 0 {
+            messages.push(format!(
+                "\x1b[90m{failed_num} 门课程的公告获取失败，可使用 announce 重试\x1b[0m\n"
+            ));
+        }
+        if let Ok(mut print) = printer.lock() {
+            for message in messages {
+                print(message);
+            }
+        }
+    });
 }
 pub fn help() {
     println!("\x1b[90m当前处于交互模式，直接输入子命令即可：\x1b[0m");
-    println!("  \x1b[32mfetch (f)\x1b[0m     拉取课件");
+    println!("  \x1b[32mfetch (f)\x1b[0m     拉取课件 [--dry-run 仅列出 | --pick 选择课件 | --retry-failed 重试失败]");
     println!("  \x1b[32msubmit (s)\x1b[0m    提交作业");
     println!("  \x1b[32mupgrade (u)\x1b[0m   更新课程列表，有新课时用");
     println!("  \x1b[32mwhich (w)\x1b[0m     选择需要拉取的课程");
     println!("  \x1b[32mtask (t)\x1b[0m      查看作业");
-    println!("  \x1b[32mgrade\x1b[0m         查看所有成绩");
-    println!("  \x1b[32mg\x1b[0m             查看本学期成绩");
-    println!("  \x1b[32mpolling (p)\x1b[0m   持续查询本学期成绩");
+    println!("  \x1b[32mannounce\x1b[0m      查看课程公告");
+    println!("  \x1b[32mcourse\x1b[0m        浏览课程活动并下载单个文件 [课程名]");
+    println!(
+        "  \x1b[32mgrade\x1b[0m         查看所有成绩 [--scale 绩点计算方式 | --major 仅专业课 | --semester 学期 | --year 学年 | --summary 学期汇总 | --chart 图表]"
+    );
+    println!(
+        "  \x1b[32mgrade export\x1b[0m  导出成绩单 [--format csv|json|md|html | --out 文件 | --gpa 附上均绩]"
+    );
+    println!("  \x1b[32mg\x1b[0m             查看本学期成绩 [同 grade]");
+    println!(
+        "  \x1b[32mgpa simulate\x1b[0m  模拟未出分课程的均绩 [--target 目标均绩 | --scale 绩点计算方式 | --major 仅专业课]"
+    );
+    println!("  \x1b[32mpolling (p)\x1b[0m   持续查询本学期成绩 [--scale 绩点计算方式 | --major 仅专业课]");
     println!(
-        "  \x1b[32mconfig (c)\x1b[0m    配置 [用户，存储目录，是否 ppt 转 pdf，是否下载 mp4 文件]"
+        "  \x1b[32mconfig (c)\x1b[0m    配置 [用户，存储目录，是否 ppt 转 pdf，是否下载 mp4 文件，并发与限速]"
     );
     println!("  \x1b[32mupdate\x1b[0m        执行更新");
     println!("  \x1b[32mversion (v)\x1b[0m   显示版本信息");
@@ -36,8 +95,32 @@ pub fn help() {
     println!("  \x1b[33mCtrl + C\x1b[0m      退出 zac");
 }
 
+/// 解析交互模式下子命令的参数
+///
+/// 解析失败(或请求帮助)时打印提示并返回 None
+pub fn parse_args<T: clap::Args + clap::FromArgMatches>(
+    name: &'static str,
+    args: &[&str],
+) -> Option<T> {
+    let command = T::augment_args(clap::Command::new(name).no_binary_name(true));
+    match command
+        .try_get_matches_from(args)
+        .and_then(|matches| T::from_arg_matches(&matches))
+    {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            let _ = e.print();
+            None
+        }
+    }
+}
+
 /// 在 fetch 之前，应当保证预登录线程 join 成功或者现有 session 可用
-pub fn fetch(settings: &utils::Settings, session: &network::Session) -> Result<()> {
+pub fn fetch(
+    settings: &utils::Settings,
+    session: &network::Session,
+    args: &command_share::FetchArgs,
+) -> Result<()> {
     process!("FETCH");
 
     let selected_courses = try_or_throw!(session.load_selected_courses(), "加载已选课程");
@@ -50,7 +133,7 @@ pub fn fetch(settings: &utils::Settings, session: &network::Session) -> Result<(
     }
 
     try_or_throw!(
-        command_share::fetch_core(settings, session, selected_courses),
+        command_share::fetch_core(settings, session, selected_courses, args),
         "FETCH"
     );
 
@@ -114,11 +197,39 @@ pub fn task(session: &network::Session) -> Result<()> {
     Ok(())
 }
 
-pub fn grade(session: &network::Session, default_account: &account::AccountData) -> Result<()> {
+pub fn course(settings: &utils::Settings, session: &network::Session, keyword: &str) -> Result<()> {
+    process!("COURSE");
+
+    try_or_throw!(
+        command_share::course_core(settings, session, keyword),
+        "COURSE"
+    );
+
+    success!("COURSE");
+
+    Ok(())
+}
+
+pub fn announce(session: &network::Session) -> Result<()> {
+    process!("ANNOUNCE");
+
+    try_or_throw!(command_share::announce_core(session), "ANNOUNCE");
+
+    success!("ANNOUNCE");
+
+    Ok(())
+}
+
+pub fn grade(
+    settings: &utils::Settings,
+    session: &network::Session,
+    default_account: &account::AccountData,
+    args: &command_share::GradeArgs,
+) -> Result<()> {
     process!("GRADE");
 
     try_or_throw!(
-        command_share::grade_core(&default_account, session),
+        command_share::grade_core(default_account, settings, session, args),
         "GRADE"
     );
 
@@ -127,22 +238,50 @@ pub fn grade(session: &network::Session, default_account: &account::AccountData)
     Ok(())
 }
 
-pub fn g(session: &network::Session, default_account: &account::AccountData) -> Result<()> {
+pub fn g(
+    settings: &utils::Settings,
+    session: &network::Session,
+    default_account: &account::AccountData,
+    args: &command_share::GradeArgs,
+) -> Result<()> {
     process!("GRADE");
 
-    try_or_throw!(command_share::g_core(default_account, session), "GRADE");
+    try_or_throw!(
+        command_share::g_core(default_account, settings, session, args),
+        "GRADE"
+    );
 
     success!("GRADE");
 
     Ok(())
 }
 
-pub fn polling(session: &network::Session, default_account: &account::AccountData) -> Result<()> {
+pub fn polling(
+    settings: &utils::Settings,
+    session: &network::Session,
+    default_account: &account::AccountData,
+    args: &command_share::GradeArgs,
+) -> Result<()> {
     process!("POLLING");
     try_or_throw!(
-        command_share::polling_core(session, default_account),
+        command_share::polling_core(session, settings, default_account, args),
         "POLLING"
     );
     success!("POLLING");
     Ok(())
 }
+
+pub fn simulate(
+    settings: &utils::Settings,
+    session: &network::Session,
+    default_account: &account::AccountData,
+    args: &command_share::SimulateArgs,
+) -> Result<()> {
+    process!("SIMULATE");
+    try_or_throw!(
+        command_share::simulate_core(default_account, settings, session, args),
+        "SIMULATE"
+    );
+    success!("SIMULATE");
+    Ok(())
+}
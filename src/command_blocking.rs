@@ -4,14 +4,15 @@ use crate::{
 };
 
 pub fn fetch(
-    default_account: &account::AccountData,
+    account: &mut account::Account,
     settings: &utils::Settings,
     session: &network::Session,
+    args: &command_share::FetchArgs,
 ) {
     process!("FETCH");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
     let selected_courses = try_or_log!(session.load_selected_courses(), "加载已选课程");
@@ -24,33 +25,33 @@ pub fn fetch(
     }
 
     try_or_log!(
-        command_share::fetch_core(settings, &session, selected_courses),
+        command_share::fetch_core(settings, session, selected_courses, args),
         "FETCH"
     );
 
     success!("FETCH");
 }
 
-pub fn submit(session: &network::Session, default_account: &account::AccountData) {
+pub fn submit(session: &network::Session, account: &mut account::Account) {
     process!("SUBMIT");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
-    try_or_log!(command_share::submit_core(&session), "SUBMIT");
+    try_or_log!(command_share::submit_core(session), "SUBMIT");
 
     success!("SUBMIT");
 }
 
-pub fn upgrade(session: &network::Session, default_account: &account::AccountData) {
+pub fn upgrade(session: &network::Session, account: &mut account::Account) {
     process!("UPGRADE");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
-    try_or_log!(command_share::upgrade_core(&session), "UPGRADE");
+    try_or_log!(command_share::upgrade_core(session), "UPGRADE");
 
     success!("UPGRADE");
 }
@@ -80,12 +81,12 @@ pub fn which(session: &network::Session) {
     success!("WHICH");
 }
 
-pub fn task(session: &network::Session, default_account: &account::AccountData) {
+pub fn task(session: &network::Session, account: &mut account::Account) {
     process!("TASK");
 
     begin!("登录");
 
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
     try_or_log!(command_share::task_core(session), "TASK");
@@ -93,38 +94,114 @@ pub fn task(session: &network::Session, default_account: &account::AccountData)
     success!("TASK");
 }
 
-pub fn grade(session: &network::Session, default_account: &account::AccountData) {
+pub fn course(
+    settings: &utils::Settings,
+    session: &network::Session,
+    account: &mut account::Account,
+    keyword: &str,
+) {
+    process!("COURSE");
+
+    begin!("登录");
+    try_or_log!(command_share::login(session, account), "登录");
+    end!("登录");
+
+    try_or_log!(
+        command_share::course_core(settings, session, keyword),
+        "COURSE"
+    );
+
+    success!("COURSE");
+}
+
+pub fn announce(session: &network::Session, account: &mut account::Account) {
+    process!("ANNOUNCE");
+
+    begin!("登录");
+    try_or_log!(command_share::login(session, account), "登录");
+    end!("登录");
+
+    try_or_log!(command_share::announce_core(session), "ANNOUNCE");
+
+    success!("ANNOUNCE");
+}
+
+pub fn grade(
+    settings: &utils::Settings,
+    session: &network::Session,
+    account: &mut account::Account,
+    args: &command_share::GradeArgs,
+) {
     process!("GRADE");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
-    try_or_log!(command_share::grade_core(default_account, session), "GRADE");
+    try_or_log!(
+        command_share::grade_core(&account.default, settings, session, args),
+        "GRADE"
+    );
 
     success!("GRADE");
 }
 
-pub fn g(session: &network::Session, default_account: &account::AccountData) {
+pub fn g(
+    settings: &utils::Settings,
+    session: &network::Session,
+    account: &mut account::Account,
+    args: &command_share::GradeArgs,
+) {
     process!("GRADE");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
-    try_or_log!(command_share::g_core(default_account, &session), "GRADE");
+    try_or_log!(
+        command_share::g_core(&account.default, settings, session, args),
+        "GRADE"
+    );
 
     success!("GRADE");
 }
 
-pub fn polling(session: &network::Session, default_account: &account::AccountData) {
+pub fn polling(
+    settings: &utils::Settings,
+    session: &network::Session,
+    account: &mut account::Account,
+    args: &command_share::GradeArgs,
+) {
     process!("POLLING");
 
     begin!("登录");
-    try_or_log!(session.login(&default_account), "登录");
+    try_or_log!(command_share::login(session, account), "登录");
     end!("登录");
 
-    try_or_log!(command_share::polling_core(&session, default_account), "POLLING");
+    try_or_log!(
+        command_share::polling_core(session, settings, &account.default, args),
+        "POLLING"
+    );
 
     success!("POLLING");
 }
+
+pub fn simulate(
+    settings: &utils::Settings,
+    session: &network::Session,
+    account: &mut account::Account,
+    args: &command_share::SimulateArgs,
+) {
+    process!("SIMULATE");
+
+    begin!("登录");
+    try_or_log!(command_share::login(session, account), "登录");
+    end!("登录");
+
+    try_or_log!(
+        command_share::simulate_core(&account.default, settings, session, args),
+        "SIMULATE"
+    );
+
+    success!("SIMULATE");
+}
@@ -1,28 +1,242 @@
 use crate::utils::{MULTISELECT_PROMPT, SELECT_PROMPT};
 use crate::{
-    account, begin, completer, end, error, network, success, try_or_throw, utils, warning,
+    account, begin, cas, completer, end, error, export, grade, gray, network, success, table,
+    try_or_throw, utils, warning,
 };
 use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use indicatif::{HumanBytes, ProgressBar};
 use std::io::Write;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use std::thread::{self, JoinHandle};
 
+/// 拉取课件
+#[derive(clap::Args, Default)]
+pub struct FetchArgs {
+    /// 仅列出待拉取的课件，不下载
+    #[arg(long, conflicts_with = "pick")]
+    pub dry_run: bool,
+    /// 选择需要拉取的课件，未选中的课件将被忽略
+    #[arg(long)]
+    pub pick: bool,
+    /// 重试此前下载失败的课件
+    #[arg(long)]
+    pub retry_failed: bool,
+}
+
+/// 查询成绩
+#[derive(clap::Args, Default)]
+pub struct GradeArgs {
+    /// 绩点计算方式，默认使用配置中的方式
+    #[arg(long, value_enum)]
+    pub scale: Option<grade::GpaScale>,
+    /// 只统计配置中的专业课
+    #[arg(long)]
+    pub major: bool,
+    /// 查看指定学期的成绩，如 2023-2024秋冬
+    #[arg(long, value_name = "学期", conflicts_with_all = ["year", "summary"])]
+    pub semester: Option<String>,
+    /// 查看指定学年的成绩，如 2023-2024
+    #[arg(long, value_name = "学年", conflicts_with = "summary")]
+    pub year: Option<String>,
+    /// 每学期一行，汇总学分、学期均绩与累计均绩
+    #[arg(long)]
+    pub summary: bool,
+    /// 画出学期均绩走势与成绩分布
+    #[arg(long, conflicts_with_all = ["semester", "year", "summary", "export"])]
+    pub chart: bool,
+    /// 导出成绩单而不是打印
+    #[arg(long, value_enum, value_name = "FORMAT", alias = "format")]
+    pub export: Option<export::ExportFormat>,
+    /// 导出到文件，默认输出到终端
+    #[arg(long, value_name = "FILE", requires = "export")]
+    pub out: Option<PathBuf>,
+    /// 导出时附上均绩汇总与每学期汇总
+    #[arg(long, requires = "export")]
+    pub gpa: bool,
+}
+
+/// 模拟均绩
+#[derive(clap::Args, Default)]
+pub struct SimulateArgs {
+    /// 绩点计算方式，默认使用配置中的方式
+    #[arg(long, value_enum)]
+    pub scale: Option<grade::GpaScale>,
+    /// 只统计配置中的专业课
+    #[arg(long)]
+    pub major: bool,
+    /// 目标均绩，求待定课程所需的最低分数
+    #[arg(long, value_name = "绩点")]
+    pub target: Option<f64>,
+}
+
+impl GradeArgs {
+    fn scheme(&self, settings: &utils::Settings) -> grade::GpaScheme {
+        grade::GpaScheme::from_settings(settings, self.scale, self.major)
+    }
+
+    /// 查看方式，没有指定学期、学年、汇总或图表时为 default
+    fn view(&self, default: network::GradeView) -> network::GradeView {
+        if let Some(semester) = &self.semester {
+            network::GradeView::Semester(semester.trim().to_string())
+        } else if let Some(year) = &self.year {
+            network::GradeView::Year(year.trim().to_string())
+        } else if self.summary {
+            network::GradeView::Summary
+        } else if self.chart {
+            network::GradeView::Chart
+        } else {
+            default
+        }
+    }
+}
+
 pub fn fetch_core(
     settings: &utils::Settings,
     session: &network::Session,
     selected_courses: Vec<network::CourseFull>,
+    args: &FetchArgs,
 ) -> Result<()> {
-    let activity_upload_record =
+    let mut activity_upload_record =
         try_or_throw!(session.load_activity_upload_record(), "加载已下载课件记录");
 
-    try_or_throw!(
-        session.fetch_activity_uploads(selected_courses, activity_upload_record, settings,),
+    let (mut tasks, mut skipped) = if args.retry_failed {
+        let tasks: Vec<network::DownloadTask> = activity_upload_record
+            .failed
+            .iter()
+            .map(|failed| failed.task.clone())
+            .collect();
+        if tasks.is_empty() {
+            warning!("没有下载失败的课件");
+            return Ok(());
+        }
+        (tasks, 0)
+    } else {
+        begin!("更新课件信息");
+        let (tasks, skipped) = try_or_throw!(
+            session.fetch_download_tasks(selected_courses, &activity_upload_record, settings),
+            "更新课件信息"
+        );
+        if tasks.is_empty() {
+            warning!("没有新课件");
+            return Ok(());
+        }
+        end!("更新课件信息");
+        (tasks, skipped)
+    };
+
+    if args.dry_run {
+        list_download_tasks(&tasks);
+        return Ok(());
+    }
+
+    if args.pick {
+        let total = tasks.len();
+        tasks = pick_download_tasks(tasks, &mut activity_upload_record, session)?;
+        if tasks.is_empty() {
+            return Ok(());
+        }
+        skipped += total - tasks.len();
+    }
+
+    let summary = try_or_throw!(
+        session.fetch_activity_uploads(tasks, activity_upload_record, settings),
         "拉取新课件"
     );
+    print_fetch_summary(&summary, skipped);
     Ok(())
 }
 
+/// 打印拉取结果统计
+fn print_fetch_summary(summary: &network::FetchSummary, skipped: usize) {
+    let mut rows = vec![
+        vec![
+            "成功".to_string(),
+            summary.succeeded.to_string(),
+            HumanBytes(summary.succeeded_bytes).to_string(),
+        ],
+        vec![
+            "失败".to_string(),
+            summary.failed.to_string(),
+            HumanBytes(summary.failed_bytes).to_string(),
+        ],
+        vec!["跳过".to_string(), skipped.to_string(), "-".to_string()],
+    ];
+    if summary.cancelled > 0 {
+        rows.push(vec![
+            "取消".to_string(),
+            summary.cancelled.to_string(),
+            "-".to_string(),
+        ]);
+    }
+    print!("{}", table::create_table(&["结果", "数量", "大小"], &rows));
+    if summary.failed > 0 {
+        warning!(
+            "有 {} 个课件下载失败，可使用 fetch --retry-failed 重试",
+            summary.failed
+        );
+    }
+}
+
+fn describe_download_task(task: &network::DownloadTask) -> String {
+    format!(
+        "{}::{} {}",
+        task.course_name,
+        task.name,
+        gray!("{}", HumanBytes(task.size))
+    )
+}
+
+/// 列出待拉取的课件
+fn list_download_tasks(tasks: &[network::DownloadTask]) {
+    for task in tasks {
+        println!("  {}", describe_download_task(task));
+    }
+    let total_size: u64 = tasks.iter().map(|task| task.size).sum();
+    success!("共 {} 个新课件，{}", tasks.len(), HumanBytes(total_size));
+}
+
+/// 选择需要拉取的课件，未选中的课件记为已忽略
+///
+/// 取消选择时不修改记录，返回空任务列表
+fn pick_download_tasks(
+    tasks: Vec<network::DownloadTask>,
+    activity_upload_record: &mut network::ActivityUploadRecord,
+    session: &network::Session,
+) -> Result<Vec<network::DownloadTask>> {
+    let items: Vec<String> = tasks.iter().map(describe_download_task).collect();
+    let indices = match MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(MULTISELECT_PROMPT)
+        .items(&items)
+        .interact_opt()
+    {
+        Ok(Some(indices)) => indices,
+        _ => {
+            warning!("取消选择课件");
+            return Ok(Vec::new());
+        }
+    };
+
+    let (picked, skipped): (Vec<_>, Vec<_>) = tasks
+        .into_iter()
+        .enumerate()
+        .partition(|(index, _)| indices.contains(index));
+
+    if !skipped.is_empty() {
+        for (_, task) in &skipped {
+            activity_upload_record.record_ignored(task);
+        }
+        try_or_throw!(
+            session.store_activity_upload_record(activity_upload_record),
+            "存储已忽略课件"
+        );
+        success!("已忽略 {} 个课件", skipped.len());
+    }
+
+    Ok(picked.into_iter().map(|(_, task)| task).collect())
+}
+
 /// 1. 异步实现获取最新作业列表
 /// 2. 选择需要上传的文件
 /// 3. 异步实现上传文件到个人资料库
@@ -134,13 +348,70 @@ fn config_help() {
     println!("  \x1b[32mremove-account (r)\x1b[0m     删除一个账户");
     println!("  \x1b[32muser-default (u)\x1b[0m       设置默认用户");
     println!("  \x1b[32mstorage-dir (s)\x1b[0m        设置存储路径");
-    println!("  \x1b[32mmp4-trashed (m)\x1b[0m        设置是否跳过下载 mp4 文件");
+    println!("  \x1b[32mmp4-trashed (m)\x1b[0m        设置是否跳过下载 mp4 文件与视频活动");
     println!("  \x1b[32mpdf-or-ppt (p)\x1b[0m         设置是否将 ppt 下载为 pdf");
+    println!("  \x1b[32mparallel-downloads (d)\x1b[0m 设置同时下载的课件数");
+    println!("  \x1b[32mparallel-requests (q)\x1b[0m  设置同时进行的 API 请求数");
+    println!("  \x1b[32mbandwidth-limit (b)\x1b[0m    设置下载带宽上限(KB/s，0 为不限制)");
+    println!("  \x1b[32mrate-limit (t)\x1b[0m         设置每秒请求数上限(0 为不限制)");
+    println!("  \x1b[32mmax-retries (e)\x1b[0m        设置网络请求最多尝试的次数");
+    println!("  \x1b[32mgpa-scale (g)\x1b[0m          设置默认的绩点计算方式");
+    println!("  \x1b[32mretake-policy (k)\x1b[0m      设置重修、补考时计入均绩的成绩");
     println!("  \x1b[32mlist-config (l)\x1b[0m        查看所有的配置");
     println!("  \x1b[32mhelp (h)\x1b[0m               显示此帮助");
     println!("  \x1b[33mCtrl + C\x1b[0m               退出配置模式");
 }
 
+/// 读入一个非负整数，输入无效时返回 None
+fn readin_number(prompt: &str) -> Option<u64> {
+    print!("{prompt}");
+    std::io::stdout().flush().unwrap();
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input).is_err() {
+        error!("读取指令失败");
+        return None;
+    }
+    match input.trim().parse() {
+        Ok(num) => Some(num),
+        Err(_) => {
+            warning!("输入无效");
+            None
+        }
+    }
+}
+
+/// 读入一行并去掉首尾空白
+fn readin_line(prompt: &str) -> Result<String> {
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// 登录默认账号
+///
+/// 若因学号或密码错误而失败(如在网页上修改过密码)，询问是否更新保存的密码并重新登录
+pub fn login(session: &network::Session, account: &mut account::Account) -> Result<()> {
+    let Err(e) = session.login(&account.default) else {
+        return Ok(());
+    };
+    if !cas::is_bad_credentials(&e) {
+        return Err(e);
+    }
+    println!();
+    error!("登录：{e}");
+    print!("是否更新保存的密码？(y/n)");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if answer.trim() != "y" {
+        return Err(e);
+    }
+    account.update_password()?;
+    session.login(&account.default)
+}
+
 /// 在 config 前，保证已经有了默认账号
 /// 为了保证稳定性，任何切换默认账号的行为都要求重新刷新课程表
 /// 这样的好处是不用返回线程了，可以直接返回新账号的会话
@@ -152,14 +423,14 @@ pub fn config_core(
 ) -> Result<()> {
     config_help();
 
-    let prompt = format!("zac/config > ");
+    let prompt = "zac/config > ".to_string();
     loop {
         let mut rl = completer::build_generic_editor(completer::CommandType::ConfigCommand);
         match rl.readline(&prompt) {
             Ok(cmd) => match cmd.as_str() {
                 "add-account" | "a" => {
                     try_or_throw!(account.add_account(settings), "添加用户");
-                    session.change_default_account(&account.default);
+                    session.change_default_account(&settings.user, &account.default);
                 }
                 "remove-account" | "r" => {
                     let users: Vec<String> = account.accounts.keys().cloned().collect();
@@ -176,7 +447,8 @@ pub fn config_core(
                                 account.remove_account(settings, user_to_delete)
                             {
                                 if is_default_changed {
-                                    session.change_default_account(&account.default);
+                                    session
+                                        .change_default_account(&settings.user, &account.default);
                                 }
                             }
                         }
@@ -206,11 +478,11 @@ pub fn config_core(
                                 continue;
                             }
 
-                            try_or_throw!(settings.set_default_user(&user_to_set,), "设置默认用户");
+                            try_or_throw!(settings.set_default_user(user_to_set,), "设置默认用户");
 
                             account.default = account.accounts.get(user_to_set).unwrap().clone();
 
-                            session.change_default_account(&account.default);
+                            session.change_default_account(&settings.user, &account.default);
                         }
                         _ => {
                             warning!("取消设置默认账号");
@@ -262,6 +534,100 @@ pub fn config_core(
                         _ => warning!("输入无效"),
                     }
                 }
+                "parallel-downloads" | "d" => {
+                    println!("当前值：{}", settings.max_parallel_downloads);
+                    let Some(num) = readin_number("同时下载的课件数：") else {
+                        continue;
+                    };
+                    try_or_throw!(
+                        settings.set_max_parallel_downloads(num as usize),
+                        "设置同时下载的课件数"
+                    );
+                }
+                "parallel-requests" | "q" => {
+                    println!("当前值：{}", settings.max_parallel_requests);
+                    let Some(num) = readin_number("同时进行的 API 请求数：") else {
+                        continue;
+                    };
+                    try_or_throw!(
+                        settings.set_max_parallel_requests(num as usize),
+                        "设置同时进行的 API 请求数"
+                    );
+                    session.apply_settings(settings);
+                }
+                "bandwidth-limit" | "b" => {
+                    println!("当前值：{} KB/s", settings.bandwidth_limit);
+                    let Some(limit) = readin_number("下载带宽上限(KB/s，0 为不限制)：")
+                    else {
+                        continue;
+                    };
+                    try_or_throw!(settings.set_bandwidth_limit(limit), "设置下载带宽上限");
+                    session.apply_settings(settings);
+                }
+                "rate-limit" | "t" => {
+                    println!("当前值：{}", settings.requests_per_second);
+                    let Some(rps) = readin_number("每秒请求数上限(0 为不限制)：")
+                    else {
+                        continue;
+                    };
+                    try_or_throw!(settings.set_requests_per_second(rps), "设置每秒请求数上限");
+                    session.apply_settings(settings);
+                }
+                "max-retries" | "e" => {
+                    println!("当前值：{}", settings.max_retries);
+                    let Some(max_retries) = readin_number("网络请求最多尝试的次数：")
+                    else {
+                        continue;
+                    };
+                    try_or_throw!(settings.set_max_retries(max_retries), "设置最多尝试次数");
+                    session.apply_settings(settings);
+                }
+                "gpa-scale" | "g" => {
+                    println!("当前值：{}", settings.gpa_scale.name());
+                    let names: Vec<&str> = grade::GpaScale::ALL
+                        .iter()
+                        .map(|scale| scale.name())
+                        .collect();
+                    if let Ok(Some(index)) = Select::with_theme(&ColorfulTheme::default())
+                        .with_prompt(SELECT_PROMPT)
+                        .items(&names)
+                        .default(
+                            grade::GpaScale::ALL
+                                .iter()
+                                .position(|scale| *scale == settings.gpa_scale)
+                                .unwrap_or(0),
+                        )
+                        .interact_opt()
+                    {
+                        try_or_throw!(
+                            settings.set_gpa_scale(grade::GpaScale::ALL[index]),
+                            "设置绩点计算方式"
+                        );
+                    }
+                }
+                "retake-policy" | "k" => {
+                    println!("当前值：{}", settings.retake_policy.name());
+                    let names: Vec<&str> = grade::RetakePolicy::ALL
+                        .iter()
+                        .map(|policy| policy.name())
+                        .collect();
+                    if let Ok(Some(index)) = Select::with_theme(&ColorfulTheme::default())
+                        .with_prompt(SELECT_PROMPT)
+                        .items(&names)
+                        .default(
+                            grade::RetakePolicy::ALL
+                                .iter()
+                                .position(|policy| *policy == settings.retake_policy)
+                                .unwrap_or(0),
+                        )
+                        .interact_opt()
+                    {
+                        try_or_throw!(
+                            settings.set_retake_policy(grade::RetakePolicy::ALL[index]),
+                            "设置重修策略"
+                        );
+                    }
+                }
                 "list-config" | "l" => {
                     try_or_throw!(settings.list(), "查看配置");
                 }
@@ -361,23 +727,377 @@ pub fn task_core(session: &network::Session) -> Result<()> {
         return Ok(());
     }
 
-    for homework in homework_list {
-        println!("  {}", homework.name);
+    let rows: Vec<Vec<String>> = homework_list.iter().map(network::Homework::row).collect();
+    print!(
+        "{}",
+        table::create_table(&["状态", "课程", "作业", "截止时间"], &rows)
+    );
+    for homework in homework_list
+        .iter()
+        .filter(|homework| !homework.description.trim().is_empty())
+    {
+        println!("{}::{}", homework.course_name, homework.title);
+        for line in homework.description.trim_end().lines() {
+            println!("\t{}", gray!("{line}"));
+        }
+    }
+    Ok(())
+}
+
+/// 浏览课程的活动，并按需下载单个文件
+///
+/// 课程名支持部分匹配，为空时可在所有课程中选择。
+/// 下载的文件不写入课件记录，不影响 fetch
+pub fn course_core(
+    settings: &utils::Settings,
+    session: &network::Session,
+    keyword: &str,
+) -> Result<()> {
+    let semester_course_map =
+        try_or_throw!(session.load_semester_course_map(), "加载 学期->课程 映射表");
+
+    let mut candidates: Vec<network::CourseFull> = semester_course_map
+        .iter()
+        .flat_map(|(semester, courses)| {
+            courses
+                .iter()
+                .filter(|course| course.name.contains(keyword))
+                .map(|course| network::CourseFull {
+                    id: course.id,
+                    semester: semester.clone(),
+                    name: course.name.clone(),
+                })
+        })
+        .collect();
+    candidates.sort_by(|a, b| network::compare_semester(&b.semester, &a.semester));
+
+    let course = match candidates.len() {
+        0 => {
+            warning!("没有找到课程 {keyword}");
+            return Ok(());
+        }
+        1 => &candidates[0],
+        _ => {
+            let rows: Vec<Vec<String>> = candidates
+                .iter()
+                .enumerate()
+                .map(|(index, course)| {
+                    vec![
+                        (index + 1).to_string(),
+                        course.semester.clone(),
+                        course.name.clone(),
+                    ]
+                })
+                .collect();
+            print!("{}", table::create_table(&["序号", "学期", "课程"], &rows));
+            let items: Vec<String> = candidates
+                .iter()
+                .map(|course| format!("{} {}", course.name, gray!("{}", course.semester)))
+                .collect();
+            match Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(SELECT_PROMPT)
+                .items(&items)
+                .default(0)
+                .interact_opt()
+            {
+                Ok(Some(index)) => &candidates[index],
+                _ => {
+                    warning!("取消选择课程");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    begin!("获取 {} 的活动列表", course.name);
+    let activities = try_or_throw!(
+        session.get_course_activities(course.id, &course.name),
+        "获取活动列表"
+    );
+    end!("获取 {} 的活动列表", course.name);
+
+    if activities.is_empty() {
+        warning!("该课程没有活动");
+        return Ok(());
+    }
+
+    let activity_items: Vec<String> = activities
+        .iter()
+        .map(|activity| {
+            format!(
+                "[{}] {} {}",
+                activity.kind_name(),
+                activity.title,
+                gray!(
+                    "{} | {} 个文件",
+                    activity.publish_time,
+                    activity.uploads.len()
+                )
+            )
+        })
+        .collect();
+    let path_download = settings
+        .storage_dir
+        .join(&course.semester)
+        .join(&course.name);
+
+    let mut activity_index = 0;
+    while let Ok(Some(index)) = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(SELECT_PROMPT)
+        .items(&activity_items)
+        .default(activity_index)
+        .interact_opt()
+    {
+        activity_index = index;
+        let activity = &activities[activity_index];
+        if activity.uploads.is_empty() {
+            warning!("该活动没有文件");
+            continue;
+        }
+
+        let upload_items: Vec<String> = activity
+            .uploads
+            .iter()
+            .map(|upload| format!("{} {}", upload.name, gray!("{}", HumanBytes(upload.size))))
+            .collect();
+        while let Ok(Some(index)) = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(SELECT_PROMPT)
+            .items(&upload_items)
+            .default(0)
+            .interact_opt()
+        {
+            let upload = &activity.uploads[index];
+
+            let pb = ProgressBar::new(0);
+            pb.set_style(network::download_progress_style());
+            pb.set_message(format!("\x1b[34m⚙\x1b[0m {}", upload.name));
+            if let Err(e) = session.download_upload(
+                &path_download,
+                upload.id,
+                &upload.name,
+                settings.is_pdf,
+                pb,
+            ) {
+                error!("下载 {} ：{e}", upload.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 查看活跃课程的公告，阅读后标记为已读
+pub fn announce_core(session: &network::Session) -> Result<()> {
+    begin!("获取课程公告");
+    let (announcements, failures) = try_or_throw!(session.get_announcements(), "获取课程公告");
+    end!("获取课程公告");
+
+    for (course_name, e) in &failures {
+        error!("获取 {course_name} 的公告：{e}");
+    }
+    if !failures.is_empty() {
+        warning!("{} 门课程的公告获取失败", failures.len());
+    }
+    if announcements.is_empty() {
+        if failures.is_empty() {
+            println!("没有公告 :)");
+        }
+        return Ok(());
+    }
+
+    let mut read_announcements = try_or_throw!(session.load_read_announcements(), "加载已读公告");
+    let unread_num = announcements
+        .iter()
+        .filter(|announcement| !read_announcements.contains(&announcement.id))
+        .count();
+    if unread_num > 0 {
+        warning!("{unread_num} 条未读公告");
+    }
+
+    let mut index = 0;
+    loop {
+        let items: Vec<String> = announcements
+            .iter()
+            .map(|announcement| {
+                let status_signal = if read_announcements.contains(&announcement.id) {
+                    "\x1b[32m✓\x1b[0m"
+                } else {
+                    "\x1b[33m!\x1b[0m"
+                };
+                format!(
+                    "{status_signal} {}::{} {}",
+                    announcement.course_name,
+                    announcement.title,
+                    gray!("{}", announcement.publish_time)
+                )
+            })
+            .collect();
+        match Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(SELECT_PROMPT)
+            .items(&items)
+            .default(index)
+            .interact_opt()
+        {
+            Ok(Some(selected)) => index = selected,
+            _ => break,
+        }
+
+        let announcement = &announcements[index];
+        println!(
+            "{}::{}\n\t{}\n{}",
+            announcement.course_name,
+            announcement.title,
+            gray!("{}", announcement.publish_time),
+            announcement.content
+        );
+        if read_announcements.insert(announcement.id) {
+            try_or_throw!(
+                session.store_read_announcements(&read_announcements),
+                "存储已读公告"
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn grade_core(
+    account: &account::AccountData,
+    settings: &utils::Settings,
+    session: &network::Session,
+    args: &GradeArgs,
+) -> Result<()> {
+    if let Some(format) = args.export {
+        let transcript = try_or_throw!(
+            session.get_transcript(
+                account,
+                args.scheme(settings),
+                &args.view(network::GradeView::All),
+                args.gpa
+            ),
+            "获取成绩列表"
+        );
+        let content = try_or_throw!(transcript.render(format), "生成成绩单");
+        match &args.out {
+            Some(out) => {
+                try_or_throw!(utils::atomic_write(out, content), "写入成绩单");
+                success!("导出成绩单 -> {}", out.display());
+            }
+            None => print!("{content}"),
+        }
+        return Ok(());
     }
+    try_or_throw!(
+        session.get_grade(
+            account,
+            args.scheme(settings),
+            &args.view(network::GradeView::All)
+        ),
+        "获取成绩列表"
+    );
     Ok(())
 }
 
-pub fn grade_core(account: &account::AccountData, session: &network::Session) -> Result<()> {
-    try_or_throw!(session.get_grade(&account), "获取成绩列表");
+pub fn g_core(
+    account: &account::AccountData,
+    settings: &utils::Settings,
+    session: &network::Session,
+    args: &GradeArgs,
+) -> Result<()> {
+    try_or_throw!(
+        session.get_grade(
+            account,
+            args.scheme(settings),
+            &args.view(network::GradeView::Active)
+        ),
+        "获取成绩列表"
+    );
     Ok(())
 }
 
-pub fn g_core(account: &account::AccountData, session: &network::Session) -> Result<()> {
-    try_or_throw!(session.get_g(&account), "获取成绩列表");
+pub fn polling_core(
+    session: &network::Session,
+    settings: &utils::Settings,
+    account: &account::AccountData,
+    args: &GradeArgs,
+) -> Result<()> {
+    try_or_throw!(
+        session.polling(account, args.scheme(settings)),
+        "持续查询成绩"
+    );
     Ok(())
 }
 
-pub fn polling_core(session: &network::Session, account: &account::AccountData) -> Result<()> {
-    try_or_throw!(session.polling(account), "持续查询成绩");
+pub fn simulate_core(
+    account: &account::AccountData,
+    settings: &utils::Settings,
+    session: &network::Session,
+    args: &SimulateArgs,
+) -> Result<()> {
+    let scheme = grade::GpaScheme::from_settings(settings, args.scale, args.major);
+    let (records, calculator, names) =
+        try_or_throw!(session.prepare_simulation(account, scheme), "获取成绩列表");
+    if calculator.current_semester().is_none() {
+        warning!("没有活跃学期，请先使用 upgrade 更新课程列表");
+        return Ok(());
+    }
+    println!(
+        "{}",
+        gray!("依次输入尚未出分课程的学分与成绩，学分留空跳过该课程，成绩留空则作为待定课程求解")
+    );
+    let mut pending = Vec::new();
+    for name in names {
+        if let Some(course) = readin_pending_course(&name, &calculator.scheme)? {
+            pending.push(course);
+        }
+    }
+    loop {
+        let name = readin_line("其他未出分的课程名(留空结束)：")?;
+        if name.is_empty() {
+            break;
+        }
+        if let Some(course) = readin_pending_course(&name, &calculator.scheme)? {
+            pending.push(course);
+        }
+    }
+    if pending.is_empty() {
+        warning!("没有需要模拟的课程");
+        return Ok(());
+    }
+    network::print_simulation(&records, &calculator, &pending, args.target);
     Ok(())
 }
+
+/// 读入一门未出分课程的学分与假设的成绩，学分留空时返回 None
+fn readin_pending_course(
+    name: &str,
+    scheme: &grade::GpaScheme,
+) -> Result<Option<grade::PendingCourse>> {
+    let credit = loop {
+        let input = readin_line(&format!("{name} 学分："))?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<f64>() {
+            Ok(credit) if credit.is_finite() && credit > 0.0 => break credit,
+            _ => warning!("输入无效"),
+        }
+    };
+    let grade = loop {
+        let input = readin_line(&format!("{name} 成绩(分数或等级)："))?;
+        if input.is_empty() {
+            break None;
+        }
+        // 只为校验成绩能否识别，学期不影响结果
+        if scheme
+            .hypothetical(name, &input, credit, ("", ""))
+            .is_some()
+        {
+            break Some(input);
+        }
+        warning!("无法识别的成绩：{input}");
+    };
+    Ok(Some(grade::PendingCourse {
+        name: name.to_string(),
+        credit,
+        grade,
+    }))
+}
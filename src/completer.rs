@@ -14,8 +14,8 @@ use std::error::Error;
 use std::path::PathBuf;
 
 const MAIN_COMMANDS: &[&str] = &[
-    "help", "fetch", "submit", "upgrade", "config", "which", "grade", "task", "version", "polling",
-    "h", "f", "s", "u", "c", "w", "g", "t", "v", "p", "update",
+    "help", "fetch", "submit", "upgrade", "config", "which", "grade", "task", "course", "announce",
+    "gpa", "version", "polling", "h", "f", "s", "u", "c", "w", "g", "t", "v", "p", "update",
 ];
 const CONFIG_MAIN_COMMANDS: &[&str] = &[
     "help",
@@ -25,6 +25,13 @@ const CONFIG_MAIN_COMMANDS: &[&str] = &[
     "storage-dir",
     "mp4-trashed",
     "pdf-or-ppt",
+    "parallel-downloads",
+    "parallel-requests",
+    "bandwidth-limit",
+    "rate-limit",
+    "max-retries",
+    "gpa-scale",
+    "retake-policy",
     "list-config",
     "a",
     "r",
@@ -32,6 +39,13 @@ const CONFIG_MAIN_COMMANDS: &[&str] = &[
     "s",
     "m",
     "p",
+    "d",
+    "q",
+    "b",
+    "t",
+    "e",
+    "g",
+    "k",
     "l",
     "h",
 ];
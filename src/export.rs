@@ -0,0 +1,350 @@
+use crate::grade::{GpaCalculator, GpaSummary, GradeRecord, Scope, SemesterSummary};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// 成绩单的导出格式
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Md,
+    Html,
+}
+
+/// 导出用的成绩单，不含任何颜色
+#[derive(Serialize)]
+pub struct Transcript {
+    /// 绩点计算方式
+    pub scale: String,
+    /// 是否只统计专业课
+    pub major_only: bool,
+    pub grades: Vec<TranscriptGrade>,
+    /// 均绩汇总
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub summary: Vec<TranscriptAverage>,
+    /// 每学期汇总
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub semesters: Vec<TranscriptSemester>,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptGrade {
+    pub semester: String,
+    pub course_code: String,
+    pub name: String,
+    pub grade: String,
+    /// 按计算方式换算的绩点，无法换算时为 None
+    pub point: Option<f64>,
+    pub credit: f64,
+    /// 所属课程分组，如 "大课"
+    pub group: Option<String>,
+    pub remarks: String,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptAverage {
+    pub label: String,
+    pub gpa: f64,
+    pub credit: f64,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptSemester {
+    pub semester: String,
+    pub credit: f64,
+    pub gpa: f64,
+    pub cumulative_gpa: f64,
+}
+
+const GRADE_HEADERS: [&str; 8] = [
+    "学期",
+    "课程代码",
+    "课程",
+    "成绩",
+    "绩点",
+    "学分",
+    "分组",
+    "备注",
+];
+const SUMMARY_HEADERS: [&str; 3] = ["项目", "均绩", "学分"];
+const SEMESTER_HEADERS: [&str; 4] = ["学期", "学分", "学期均绩", "累计均绩"];
+
+impl Transcript {
+    /// 以 scope 范围内的成绩建立成绩单
+    pub fn new(records: &[GradeRecord], calculator: &GpaCalculator, scope: Scope) -> Transcript {
+        let scheme = &calculator.scheme;
+        Transcript {
+            scale: scheme.scale.name().to_string(),
+            major_only: scheme.major_only,
+            grades: records
+                .iter()
+                .filter(|record| calculator.in_scope(record, scope))
+                .map(|record| TranscriptGrade {
+                    semester: record.semester(),
+                    course_code: record.course_code.clone(),
+                    name: record.name.clone(),
+                    grade: record.grade.clone(),
+                    point: scheme.point(record),
+                    credit: record.credit,
+                    group: scheme
+                        .group_of(record)
+                        .map(|index| scheme.groups()[index].name.clone()),
+                    remarks: record.remarks(),
+                })
+                .collect(),
+            summary: Vec::new(),
+            semesters: Vec::new(),
+        }
+    }
+
+    /// 附上均绩汇总
+    pub fn add_summary(&mut self, summary: &GpaSummary, scope: Scope) {
+        self.summary = summary
+            .lines(scope)
+            .into_iter()
+            .map(|line| TranscriptAverage {
+                label: line.label,
+                gpa: line.average.value(),
+                credit: line.average.total_credit,
+            })
+            .collect();
+    }
+
+    /// 附上每学期汇总
+    pub fn add_semesters(&mut self, semesters: &[SemesterSummary]) {
+        self.semesters = semesters
+            .iter()
+            .map(|summary| TranscriptSemester {
+                semester: summary.semester.clone(),
+                credit: summary.term.total_credit,
+                gpa: summary.term.value(),
+                cumulative_gpa: summary.cumulative.value(),
+            })
+            .collect();
+    }
+
+    /// 按 format 输出
+    pub fn render(&self, format: ExportFormat) -> Result<String> {
+        Ok(match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+            ExportFormat::Csv => {
+                let body = self.sections(|headers, rows| {
+                    let mut csv = csv_line(headers.iter().copied());
+                    for row in rows {
+                        csv.push_str(&csv_line(row.iter().map(String::as_str)));
+                    }
+                    csv
+                });
+                let note = self.scale_note();
+                format!("{}\n{body}", csv_line(["绩点", note.as_str()].into_iter()))
+            }
+            ExportFormat::Md => {
+                let body = self.sections(|headers, rows| {
+                    let mut md = md_line(headers.iter().copied());
+                    md.push_str(&md_line(headers.iter().map(|_| "---")));
+                    for row in rows {
+                        md.push_str(&md_line(row.iter().map(String::as_str)));
+                    }
+                    md
+                });
+                format!("绩点：{}\n\n{body}", self.scale_note())
+            }
+            ExportFormat::Html => {
+                let body = self.sections(|headers, rows| {
+                    let mut html = String::from("<table>\n<tr>");
+                    for header in headers {
+                        html.push_str(&format!("<th>{}</th>", html_escape(header)));
+                    }
+                    html.push_str("</tr>\n");
+                    for row in rows {
+                        html.push_str("<tr>");
+                        for cell in row {
+                            html.push_str(&format!("<td>{}</td>", html_escape(cell)));
+                        }
+                        html.push_str("</tr>\n");
+                    }
+                    html.push_str("</table>\n");
+                    html
+                });
+                format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>成绩单</title>\n</head>\n<body>\n<p>绩点：{}</p>\n{body}</body>\n</html>\n",
+                    html_escape(&self.scale_note())
+                )
+            }
+        })
+    }
+
+    /// 依次渲染成绩、均绩汇总与每学期汇总三个表格，以空行分隔
+    fn sections(&self, table: impl Fn(&[&str], &[Vec<String>]) -> String) -> String {
+        let mut sections = vec![table(&GRADE_HEADERS, &self.grade_rows())];
+        if !self.summary.is_empty() {
+            let rows: Vec<Vec<String>> = self
+                .summary
+                .iter()
+                .map(|average| {
+                    vec![
+                        average.label.clone(),
+                        format!("{:.2}", average.gpa),
+                        format!("{:.1}", average.credit),
+                    ]
+                })
+                .collect();
+            sections.push(table(&SUMMARY_HEADERS, &rows));
+        }
+        if !self.semesters.is_empty() {
+            let rows: Vec<Vec<String>> = self
+                .semesters
+                .iter()
+                .map(|semester| {
+                    vec![
+                        semester.semester.clone(),
+                        format!("{:.1}", semester.credit),
+                        format!("{:.2}", semester.gpa),
+                        format!("{:.2}", semester.cumulative_gpa),
+                    ]
+                })
+                .collect();
+            sections.push(table(&SEMESTER_HEADERS, &rows));
+        }
+        sections.join("\n")
+    }
+
+    fn grade_rows(&self) -> Vec<Vec<String>> {
+        self.grades
+            .iter()
+            .map(|grade| {
+                vec![
+                    grade.semester.clone(),
+                    grade.course_code.clone(),
+                    grade.name.clone(),
+                    grade.grade.clone(),
+                    grade
+                        .point
+                        .map(|point| format!("{point:.2}"))
+                        .unwrap_or_default(),
+                    format!("{:.1}", grade.credit),
+                    grade.group.clone().unwrap_or_default(),
+                    grade.remarks.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    /// 如 "4.0，仅专业课"
+    fn scale_note(&self) -> String {
+        if self.major_only {
+            format!("{}，仅专业课", self.scale)
+        } else {
+            self.scale.clone()
+        }
+    }
+}
+
+/// CSV 的一行，含逗号、引号或换行(含 \\r)的字段加引号
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+/// Markdown 表格的一行，转义其中的竖线
+fn md_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields.map(|field| field.replace('|', "\\|")).collect();
+    format!("| {} |\n", fields.join(" | "))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        Transcript {
+            scale: "4.3".to_string(),
+            major_only: true,
+            grades: vec![TranscriptGrade {
+                semester: "2023-2024 秋".to_string(),
+                course_code: "211G0200".to_string(),
+                name: "Tom & \"Jerry\", <I|II>".to_string(),
+                grade: "95".to_string(),
+                point: Some(4.3),
+                credit: 2.0,
+                group: None,
+                remarks: "补考\r\n重修".to_string(),
+            }],
+            summary: Vec::new(),
+            semesters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn csv_quotes_special_fields() {
+        assert_eq!(csv_line(["a", "b,c"].into_iter()), "a,\"b,c\"\n");
+        assert_eq!(csv_line(["say \"hi\""].into_iter()), "\"say \"\"hi\"\"\"\n");
+        assert_eq!(csv_line(["a\rb"].into_iter()), "\"a\rb\"\n");
+        assert_eq!(csv_line(["a\nb"].into_iter()), "\"a\nb\"\n");
+
+        let csv = transcript().render(ExportFormat::Csv).unwrap();
+        assert!(csv.starts_with("绩点,4.3，仅专业课\n\n"));
+        assert!(csv.contains("\"Tom & \"\"Jerry\"\", <I|II>\""));
+        assert!(csv.contains("\"补考\r\n重修\""));
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        assert_eq!(md_line(["a|b", "c"].into_iter()), "| a\\|b | c |\n");
+
+        let md = transcript().render(ExportFormat::Md).unwrap();
+        assert!(md.starts_with("绩点：4.3，仅专业课\n\n"));
+        assert!(md.contains("Tom & \"Jerry\", <I\\|II>"));
+    }
+
+    #[test]
+    fn html_escapes_markup() {
+        assert_eq!(
+            html_escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+
+        let html = transcript().render(ExportFormat::Html).unwrap();
+        assert!(html.contains("<p>绩点：4.3，仅专业课</p>"));
+        assert!(html.contains("<td>Tom &amp; &quot;Jerry&quot;, &lt;I|II&gt;</td>"));
+    }
+
+    #[test]
+    fn json_omits_empty_summary_and_semesters() {
+        let mut transcript = transcript();
+        let json = transcript.render(ExportFormat::Json).unwrap();
+        assert!(!json.contains("\"summary\""));
+        assert!(!json.contains("\"semesters\""));
+
+        transcript.summary.push(TranscriptAverage {
+            label: "总均绩".to_string(),
+            gpa: 4.3,
+            credit: 2.0,
+        });
+        transcript.semesters.push(TranscriptSemester {
+            semester: "2023-2024 秋".to_string(),
+            credit: 2.0,
+            gpa: 4.3,
+            cumulative_gpa: 4.3,
+        });
+        let json = transcript.render(ExportFormat::Json).unwrap();
+        assert!(json.contains("\"summary\""));
+        assert!(json.contains("\"semesters\""));
+    }
+}
@@ -0,0 +1,1230 @@
+use crate::{network, utils};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// 一条成绩记录，由成绩查询接口返回的 JSON 解析而来
+#[derive(Clone)]
+pub struct GradeRecord {
+    /// 课程代码
+    pub course_code: String,
+    /// 课程名
+    pub name: String,
+    /// 原始成绩，可能是百分制分数，也可能是“优秀”“合格”等等级
+    pub grade: String,
+    /// 百分制分数，等级制成绩为 None
+    pub score: Option<f64>,
+    /// 学分
+    pub credit: f64,
+    /// 五分制绩点
+    pub gpa: f64,
+    /// 学年，如 "2024-2025"
+    pub year: String,
+    /// 学期，如 "春夏"
+    pub term: String,
+    /// 考试性质：正常、重修或补考
+    pub attempt: Attempt,
+    /// 是否已被同一课程的其他成绩替代，替代的成绩不计入均绩
+    pub superseded: bool,
+}
+
+/// 考试性质
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    Normal,
+    /// 重修
+    Retake,
+    /// 补考
+    MakeUp,
+}
+
+impl GradeRecord {
+    /// 解析一条成绩，字段缺失或格式不对时返回 None
+    pub fn from_json(value: &Value) -> Option<GradeRecord> {
+        let obj = value.as_object()?;
+        let grade = obj.get("cj")?.as_str()?.trim().to_string();
+        let credit = match obj.get("xf")? {
+            Value::String(credit) => credit.trim().parse().ok()?,
+            credit => credit.as_f64()?,
+        };
+        let course_code = obj
+            .get("kcdm")
+            .and_then(|v| v.as_str())
+            .map(|code| code.to_string())
+            .or_else(|| {
+                obj.get("xkkh")
+                    .and_then(|v| v.as_str())
+                    .and_then(course_code_from_xkkh)
+            })
+            .unwrap_or_default();
+        // 考试性质，如 "正常考试"、"重修"、"补考"
+        let attempt = match obj.get("ksxz").and_then(|v| v.as_str()) {
+            Some(kind) if kind.contains("补考") => Attempt::MakeUp,
+            Some(kind) if kind.contains("重修") => Attempt::Retake,
+            _ => Attempt::Normal,
+        };
+        Some(GradeRecord {
+            course_code,
+            name: obj.get("kcmc")?.as_str()?.to_string(),
+            score: grade.parse().ok(),
+            grade,
+            credit,
+            gpa: obj.get("jd")?.as_f64()?,
+            year: obj.get("xn")?.as_str()?.to_string(),
+            term: obj.get("xq")?.as_str()?.to_string(),
+            attempt,
+            superseded: false,
+        })
+    }
+
+    /// 解析全部成绩，跳过无法解析的记录与弃修的课程
+    pub fn parse_all(values: &[Value]) -> Vec<GradeRecord> {
+        values
+            .iter()
+            .filter_map(GradeRecord::from_json)
+            .filter(|record| !record.is_dropped())
+            .collect()
+    }
+
+    /// 是否为弃修
+    pub fn is_dropped(&self) -> bool {
+        self.grade == "弃修"
+    }
+
+    /// 是否为合格/不合格(通过/不通过)制的成绩，这类成绩不计入均绩
+    pub fn is_pass_fail(&self) -> bool {
+        matches!(self.grade.as_str(), "合格" | "不合格" | "通过" | "不通过")
+    }
+
+    /// 合格制的成绩是否通过
+    pub fn passed(&self) -> bool {
+        matches!(self.grade.as_str(), "合格" | "通过")
+    }
+
+    /// 同一课程的不同成绩以课程代码归为一组，没有课程代码时使用课程名
+    fn course_key(&self) -> &str {
+        if self.course_code.is_empty() {
+            &self.name
+        } else {
+            &self.course_code
+        }
+    }
+
+    /// 表格中的备注，如 "重修"、"已被替代"
+    pub fn remarks(&self) -> String {
+        let mut remarks = Vec::new();
+        match self.attempt {
+            Attempt::Retake => remarks.push("重修"),
+            Attempt::MakeUp => remarks.push("补考"),
+            Attempt::Normal => {}
+        }
+        if self.is_pass_fail() {
+            remarks.push("不计绩点");
+        }
+        if self.superseded {
+            remarks.push("已被替代");
+        }
+        remarks.join("，")
+    }
+
+    /// 所在学期，如 "2024-2025春夏"
+    pub fn semester(&self) -> String {
+        format!("{}{}", self.year, self.term)
+    }
+}
+
+/// 同一课程有多次成绩(重修、补考)时，计入均绩的那一次
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetakePolicy {
+    /// 最近一次
+    #[default]
+    Latest,
+    /// 绩点最高的一次
+    Highest,
+    /// 第一次
+    First,
+}
+
+impl RetakePolicy {
+    pub const ALL: [RetakePolicy; 3] = [
+        RetakePolicy::Latest,
+        RetakePolicy::Highest,
+        RetakePolicy::First,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RetakePolicy::Latest => "最近一次",
+            RetakePolicy::Highest => "最高的一次",
+            RetakePolicy::First => "第一次",
+        }
+    }
+
+    /// 按策略处理重修与补考
+    ///
+    /// 同一课程的成绩按学期排序，同一学期中补考/重修排在正常考试之后；
+    /// 后来的正常考试成绩视为重修。除策略选中的一次外，其余成绩标记为已被替代
+    pub fn apply(&self, records: &mut [GradeRecord]) {
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, record) in records.iter().enumerate() {
+            groups
+                .entry(record.course_key().to_string())
+                .or_default()
+                .push(index);
+        }
+        for mut group in groups.into_values().filter(|group| group.len() > 1) {
+            group.sort_by(|&a, &b| {
+                let (a, b) = (&records[a], &records[b]);
+                network::compare_semester(&a.semester(), &b.semester())
+                    .then((a.attempt != Attempt::Normal).cmp(&(b.attempt != Attempt::Normal)))
+            });
+            for &index in &group[1..] {
+                if records[index].attempt == Attempt::Normal {
+                    records[index].attempt = Attempt::Retake;
+                }
+            }
+            let chosen = match self {
+                RetakePolicy::Latest => *group.last().unwrap(),
+                RetakePolicy::First => group[0],
+                // 绩点相同时取较晚的一次(max_by 返回最后一个最大值)
+                RetakePolicy::Highest => *group
+                    .iter()
+                    .max_by(|&&a, &&b| records[a].gpa.total_cmp(&records[b].gpa))
+                    .unwrap(),
+            };
+            for index in group {
+                records[index].superseded = index != chosen;
+            }
+        }
+    }
+}
+
+/// 从选课课号 "(2024-2025-1)-MATH1136G-0094444-1" 中取出课程代码
+fn course_code_from_xkkh(xkkh: &str) -> Option<String> {
+    let rest = xkkh.split_once(")-")?.1;
+    Some(rest.split('-').next()?.to_string())
+}
+
+/// 百分制分数对应的五分制绩点：95 分及以上为 5.0，60 分为 1.5，每分 0.1，不及格为 0
+pub fn five_point(score: f64) -> f64 {
+    if score < 60.0 {
+        0.0
+    } else {
+        ((score.floor() - 45.0) / 10.0).min(5.0)
+    }
+}
+
+/// 课程分组，如大课、中课、小课，分别统计均绩
+///
+/// 课程代码（前缀）或课程名在 courses 中的课程属于该组；
+/// courses 为空时按学分划分，学分在 [min_credit, max_credit] 内的课程属于该组，未填的一端不限
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CourseGroup {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_credit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_credit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub courses: Vec<String>,
+}
+
+impl CourseGroup {
+    fn by_credit(name: &str, min_credit: Option<f64>, max_credit: Option<f64>) -> CourseGroup {
+        CourseGroup {
+            name: name.to_string(),
+            min_credit,
+            max_credit,
+            courses: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, record: &GradeRecord) -> bool {
+        if !self.courses.is_empty() {
+            return matches_course(&self.courses, record);
+        }
+        self.min_credit.is_none_or(|min| record.credit >= min)
+            && self.max_credit.is_none_or(|max| record.credit <= max)
+    }
+}
+
+/// 默认的课程分组：3.5 至 7 学分为大课，2 至 3.5 学分为中课，其余为小课
+pub fn default_course_groups() -> Vec<CourseGroup> {
+    vec![
+        CourseGroup::by_credit("大课", Some(3.5), Some(7.0)),
+        CourseGroup::by_credit("中课", Some(2.0), Some(3.5)),
+        CourseGroup::by_credit("小课", None, None),
+    ]
+}
+
+/// 课程代码或课程名与 courses 中的某一项相同，或课程代码以其为前缀
+fn matches_course(courses: &[String], record: &GradeRecord) -> bool {
+    courses.iter().any(|course| {
+        *course == record.name
+            || (!record.course_code.is_empty() && record.course_code.starts_with(course))
+    })
+}
+
+/// 绩点的计算方式
+///
+/// 各取值不写文档注释，以免命令行帮助展开成长格式
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpaScale {
+    // 教务系统给出的五分制绩点
+    #[default]
+    Five,
+    // 由百分制分数换算的 4.0 绩点
+    Four,
+    // 由百分制分数换算的 4.3 绩点
+    FourThree,
+    // 百分制分数
+    Hundred,
+    // 按设置中的自定义绩点表换算
+    Custom,
+}
+
+impl GpaScale {
+    pub const ALL: [GpaScale; 5] = [
+        GpaScale::Five,
+        GpaScale::Four,
+        GpaScale::FourThree,
+        GpaScale::Hundred,
+        GpaScale::Custom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GpaScale::Five => "五分制",
+            GpaScale::Four => "4.0",
+            GpaScale::FourThree => "4.3",
+            GpaScale::Hundred => "百分制",
+            GpaScale::Custom => "自定义",
+        }
+    }
+
+    /// 满分，自定义绩点表按其中最高的绩点计
+    pub fn max(&self, custom_scale: &[ScaleStep]) -> f64 {
+        match self {
+            GpaScale::Five => 5.0,
+            GpaScale::Four => 4.0,
+            GpaScale::FourThree => 4.3,
+            GpaScale::Hundred => 100.0,
+            GpaScale::Custom => custom_scale
+                .iter()
+                .map(|step| step.point)
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+/// 绩点表中的一档：分数不低于 min_score 时记为 point
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ScaleStep {
+    pub min_score: f64,
+    pub point: f64,
+}
+
+/// 4.0 绩点表
+const FOUR_SCALE: [ScaleStep; 10] = [
+    ScaleStep {
+        min_score: 90.0,
+        point: 4.0,
+    },
+    ScaleStep {
+        min_score: 85.0,
+        point: 3.7,
+    },
+    ScaleStep {
+        min_score: 82.0,
+        point: 3.3,
+    },
+    ScaleStep {
+        min_score: 78.0,
+        point: 3.0,
+    },
+    ScaleStep {
+        min_score: 75.0,
+        point: 2.7,
+    },
+    ScaleStep {
+        min_score: 72.0,
+        point: 2.3,
+    },
+    ScaleStep {
+        min_score: 68.0,
+        point: 2.0,
+    },
+    ScaleStep {
+        min_score: 64.0,
+        point: 1.5,
+    },
+    ScaleStep {
+        min_score: 60.0,
+        point: 1.0,
+    },
+    ScaleStep {
+        min_score: 0.0,
+        point: 0.0,
+    },
+];
+
+/// 4.3 绩点表中高于 4.0 的一档，其余与 4.0 绩点表相同
+const FOUR_THREE_TOP: ScaleStep = ScaleStep {
+    min_score: 95.0,
+    point: 4.3,
+};
+
+/// 按绩点表换算分数，表中没有匹配的档位时记为 0
+fn lookup(scale: &[ScaleStep], score: f64) -> f64 {
+    scale
+        .iter()
+        .filter(|step| score >= step.min_score)
+        .map(|step| step.point)
+        .fold(0.0, f64::max)
+}
+
+/// 等级制成绩默认对应的百分制分数
+pub fn default_grade_mapping() -> BTreeMap<String, f64> {
+    [
+        ("优秀", 95.0),
+        ("良好", 85.0),
+        ("中等", 75.0),
+        ("及格", 65.0),
+        ("不及格", 0.0),
+    ]
+    .into_iter()
+    .map(|(grade, score)| (grade.to_string(), score))
+    .collect()
+}
+
+/// 默认的自定义绩点表，与 4.0 绩点表相同
+pub fn default_custom_scale() -> Vec<ScaleStep> {
+    FOUR_SCALE.to_vec()
+}
+
+/// 均绩的计算方案：绩点的计算方式，以及是否只统计专业课
+pub struct GpaScheme {
+    pub scale: GpaScale,
+    /// 是否只统计专业课
+    pub major_only: bool,
+    grade_mapping: BTreeMap<String, f64>,
+    custom_scale: Vec<ScaleStep>,
+    major_courses: Vec<String>,
+    retake_policy: RetakePolicy,
+    course_groups: Vec<CourseGroup>,
+}
+
+impl GpaScheme {
+    /// 按设置建立方案，scale 为 None 时使用设置中的默认计算方式
+    pub fn from_settings(
+        settings: &utils::Settings,
+        scale: Option<GpaScale>,
+        major_only: bool,
+    ) -> GpaScheme {
+        GpaScheme {
+            scale: scale.unwrap_or(settings.gpa_scale),
+            major_only,
+            grade_mapping: settings.grade_mapping.clone(),
+            custom_scale: settings.custom_scale.clone(),
+            major_courses: settings.major_courses.clone(),
+            retake_policy: settings.retake_policy,
+            course_groups: settings.course_groups.clone(),
+        }
+    }
+
+    /// 按设置中的重修策略标记被替代的成绩
+    pub fn resolve_retakes(&self, records: &mut [GradeRecord]) {
+        self.retake_policy.apply(records);
+    }
+
+    /// 成绩对应的百分制分数，等级制成绩按设置中的映射表换算
+    pub fn score(&self, record: &GradeRecord) -> Option<f64> {
+        record
+            .score
+            .or_else(|| self.grade_mapping.get(&record.grade).copied())
+    }
+
+    /// 成绩在此方案下的绩点，无法换算时为 None
+    pub fn point(&self, record: &GradeRecord) -> Option<f64> {
+        match self.scale {
+            GpaScale::Five => Some(record.gpa),
+            GpaScale::Four => Some(lookup(&FOUR_SCALE, self.score(record)?)),
+            GpaScale::FourThree => {
+                let score = self.score(record)?;
+                Some(lookup(&[FOUR_THREE_TOP], score).max(lookup(&FOUR_SCALE, score)))
+            }
+            GpaScale::Hundred => self.score(record),
+            GpaScale::Custom => Some(lookup(&self.custom_scale, self.score(record)?)),
+        }
+    }
+
+    /// 满分
+    pub fn max(&self) -> f64 {
+        self.scale.max(&self.custom_scale)
+    }
+
+    /// 是否为专业课：课程代码或课程名与设置中的某一项相同，或课程代码以其为前缀
+    pub fn is_major(&self, record: &GradeRecord) -> bool {
+        matches_course(&self.major_courses, record)
+    }
+
+    /// 设置中的课程分组
+    pub fn groups(&self) -> &[CourseGroup] {
+        &self.course_groups
+    }
+
+    /// 课程所属分组的序号，按设置中的顺序取第一个匹配的分组，都不匹配时为 None
+    pub fn group_of(&self, record: &GradeRecord) -> Option<usize> {
+        self.course_groups
+            .iter()
+            .position(|group| group.contains(record))
+    }
+
+    /// 成绩是否参与统计
+    pub fn counts(&self, record: &GradeRecord) -> bool {
+        !record.superseded && (!self.major_only || self.is_major(record))
+    }
+
+    /// 计入均绩的绩点，被替代、不参与统计、合格制或无法换算的成绩为 None
+    pub fn counted_point(&self, record: &GradeRecord) -> Option<f64> {
+        if !self.counts(record) || record.is_pass_fail() {
+            return None;
+        }
+        self.point(record)
+    }
+
+    /// 以假设的成绩建立一条记录，grade 为百分制分数、映射表中的等级或合格制成绩，无法识别时返回 None
+    pub fn hypothetical(
+        &self,
+        name: &str,
+        grade: &str,
+        credit: f64,
+        (year, term): (&str, &str),
+    ) -> Option<GradeRecord> {
+        let grade = grade.trim();
+        let score: Option<f64> = grade.parse().ok();
+        if score.is_some_and(|score| !(0.0..=100.0).contains(&score)) {
+            return None;
+        }
+        let mut record = GradeRecord {
+            course_code: String::new(),
+            name: name.to_string(),
+            grade: grade.to_string(),
+            score,
+            credit,
+            gpa: 0.0,
+            year: year.to_string(),
+            term: term.to_string(),
+            attempt: Attempt::Normal,
+            superseded: false,
+        };
+        if !record.is_pass_fail() {
+            record.gpa = five_point(self.score(&record)?);
+        }
+        Some(record)
+    }
+
+    /// 绩点可能发生变化的分数，从低到高
+    ///
+    /// 五分制按整数分换算，4.0、4.3 绩点表的各档也都是整数分；自定义绩点表另加各档的最低分；
+    /// 百分制的均绩随分数连续变化，精确到 0.01 分
+    fn candidate_scores(&self) -> Vec<f64> {
+        let mut scores: Vec<f64> = match self.scale {
+            GpaScale::Hundred => (0..=10000).map(|score| f64::from(score) / 100.0).collect(),
+            _ => (0..=100).map(f64::from).collect(),
+        };
+        if self.scale == GpaScale::Custom {
+            scores.extend(
+                self.custom_scale
+                    .iter()
+                    .map(|step| step.min_score)
+                    .filter(|score| (0.0..=100.0).contains(score)),
+            );
+            scores.sort_by(f64::total_cmp);
+            scores.dedup();
+        }
+        scores
+    }
+
+    /// 是否不计绩点但计学分，即通过的合格制成绩
+    pub fn counts_credit(&self, record: &GradeRecord) -> bool {
+        self.counts(record) && record.is_pass_fail() && record.passed()
+    }
+}
+
+/// 按学分加权的平均
+#[derive(Default, Clone, Copy)]
+pub struct Average {
+    /// 加权和
+    pub weighted: f64,
+    /// 计入平均的学分和
+    pub credit: f64,
+    /// 获得的总学分，包括不计绩点的合格制课程
+    pub total_credit: f64,
+}
+
+impl Average {
+    pub fn add(&mut self, value: f64, credit: f64) {
+        self.weighted += value * credit;
+        self.credit += credit;
+        self.total_credit += credit;
+    }
+
+    /// 只计学分，不计入平均
+    pub fn add_credit(&mut self, credit: f64) {
+        self.total_credit += credit;
+    }
+
+    /// 加权平均值，没有学分时为 0
+    pub fn value(&self) -> f64 {
+        if self.credit == 0.0 {
+            0.0
+        } else {
+            self.weighted / self.credit
+        }
+    }
+}
+
+/// 一组成绩的均绩汇总
+#[derive(Default)]
+pub struct GpaSummary {
+    /// 总均绩
+    pub overall: Average,
+    /// 当前学年均绩
+    pub year: Average,
+    /// 当前学期均绩
+    pub term: Average,
+    /// 各课程分组的均绩，顺序与设置中的分组一致
+    pub groups: Vec<(String, Average)>,
+}
+
+/// 汇总中的一行，如 "学期均绩"
+pub struct SummaryLine {
+    pub label: String,
+    /// 按课程分组统计的均绩所对应的分组序号
+    pub group: Option<usize>,
+    pub average: Average,
+}
+
+impl GpaSummary {
+    /// scope 范围内的均绩
+    pub fn average(&self, scope: Scope) -> Average {
+        match scope {
+            Scope::All => self.overall,
+            Scope::Year => self.year,
+            Scope::Term => self.term,
+        }
+    }
+
+    /// 按统计范围列出需要展示的均绩
+    pub fn lines(&self, scope: Scope) -> Vec<SummaryLine> {
+        let line = |label: &str, group, average: &Average| SummaryLine {
+            label: label.to_string(),
+            group,
+            average: *average,
+        };
+        let prefix = match scope {
+            Scope::All => "",
+            Scope::Year => "学年",
+            Scope::Term => "学期",
+        };
+        let mut lines = Vec::new();
+        if scope != Scope::Year {
+            lines.push(line("学期均绩", None, &self.term));
+        }
+        lines.push(line("学年均绩", None, &self.year));
+        lines.push(line("总均绩", None, &self.overall));
+        for (index, (name, average)) in self.groups.iter().enumerate() {
+            lines.push(line(&format!("{prefix}{name}均绩"), Some(index), average));
+        }
+        lines
+    }
+}
+
+/// 统计的范围
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// 全部成绩
+    All,
+    /// 当前学年
+    Year,
+    /// 当前学期
+    Term,
+}
+
+/// 均绩计算器
+///
+/// 记住当前的学年与学期以及计算方案，统一计算总均绩、学年均绩、学期均绩与各类课程的均绩
+pub struct GpaCalculator {
+    years: HashSet<String>,
+    terms: HashSet<String>,
+    /// 当前学期(学年, 学期)，如 ("2024-2025", "秋冬")
+    current: Option<(String, String)>,
+    pub scheme: GpaScheme,
+}
+
+impl GpaCalculator {
+    /// years 与 terms 为当前活跃的学年与学期，current 为当前学期
+    pub fn new(
+        years: HashSet<String>,
+        terms: HashSet<String>,
+        current: Option<(String, String)>,
+        scheme: GpaScheme,
+    ) -> GpaCalculator {
+        GpaCalculator {
+            years,
+            terms,
+            current,
+            scheme,
+        }
+    }
+
+    /// 成绩是否属于当前学年
+    pub fn in_year(&self, record: &GradeRecord) -> bool {
+        self.years.contains(&record.year)
+    }
+
+    /// 成绩是否属于当前学期
+    pub fn in_term(&self, record: &GradeRecord) -> bool {
+        self.in_year(record) && self.terms.contains(&record.term)
+    }
+
+    /// 成绩是否在 scope 范围内
+    pub fn in_scope(&self, record: &GradeRecord, scope: Scope) -> bool {
+        match scope {
+            Scope::All => true,
+            Scope::Year => self.in_year(record),
+            Scope::Term => self.in_term(record),
+        }
+    }
+
+    /// 计算均绩，各课程分组的均绩只统计 scope 范围内的成绩
+    pub fn summarize(&self, records: &[GradeRecord], scope: Scope) -> GpaSummary {
+        let mut summary = GpaSummary {
+            groups: self
+                .scheme
+                .groups()
+                .iter()
+                .map(|group| (group.name.clone(), Average::default()))
+                .collect(),
+            ..Default::default()
+        };
+        for record in records {
+            // 合格制成绩不计绩点，通过时仍计学分
+            let point = self.scheme.counted_point(record);
+            if point.is_none() && !self.scheme.counts_credit(record) {
+                continue;
+            }
+            let credit = record.credit;
+            let add = |average: &mut Average| match point {
+                Some(point) => average.add(point, credit),
+                None => average.add_credit(credit),
+            };
+            add(&mut summary.overall);
+            if self.in_year(record) {
+                add(&mut summary.year);
+            }
+            if self.in_term(record) {
+                add(&mut summary.term);
+            }
+            if let Some(index) = self.scheme.group_of(record) {
+                if self.in_scope(record, scope) {
+                    add(&mut summary.groups[index].1);
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// 尚未出分的课程
+pub struct PendingCourse {
+    pub name: String,
+    pub credit: f64,
+    /// 假设的成绩，None 表示待求解
+    pub grade: Option<String>,
+}
+
+impl GpaCalculator {
+    /// 当前学期，用作尚未出分课程的学年学期
+    pub fn current_semester(&self) -> Option<(&str, &str)> {
+        let (year, term) = self.current.as_ref()?;
+        Some((year, term))
+    }
+
+    /// 在已有成绩上加入尚未出分课程的假设成绩后计算均绩
+    ///
+    /// 待求解的课程取 unknown_score 分，为 None 时不计入
+    pub fn simulate(
+        &self,
+        records: &[GradeRecord],
+        pending: &[PendingCourse],
+        unknown_score: Option<f64>,
+    ) -> GpaSummary {
+        let mut records = records.to_vec();
+        if let Some(semester) = self.current_semester() {
+            let unknown_grade = unknown_score.map(|score| score.to_string());
+            records.extend(pending.iter().filter_map(|course| {
+                let grade = course.grade.as_ref().or(unknown_grade.as_ref())?;
+                self.scheme
+                    .hypothetical(&course.name, grade, course.credit, semester)
+            }));
+        }
+        self.scheme.resolve_retakes(&mut records);
+        self.summarize(&records, Scope::Term)
+    }
+
+    /// 待求解的课程至少都需要多少分，才能使 scope 范围内的均绩达到 target，满分也达不到时为 None
+    ///
+    /// 均绩随分数单调不减，在绩点可能变化的分数中二分查找
+    pub fn min_required_score(
+        &self,
+        records: &[GradeRecord],
+        pending: &[PendingCourse],
+        target: f64,
+        scope: Scope,
+    ) -> Option<f64> {
+        let scores = self.scheme.candidate_scores();
+        let index = scores.partition_point(|&score| {
+            let summary = self.simulate(records, pending, Some(score));
+            summary.average(scope).value() < target - 1e-9
+        });
+        scores.get(index).copied()
+    }
+}
+
+/// 一个学期的汇总
+pub struct SemesterSummary {
+    /// 学期，如 "2024-2025春夏"
+    pub semester: String,
+    /// 本学期
+    pub term: Average,
+    /// 截至本学期的累计
+    pub cumulative: Average,
+}
+
+/// 按学期先后汇总每学期的学分、学期均绩与累计均绩
+pub fn semester_summaries(records: &[GradeRecord], scheme: &GpaScheme) -> Vec<SemesterSummary> {
+    let mut semesters: Vec<String> = records.iter().map(|record| record.semester()).collect();
+    semesters.sort_by(|a, b| network::compare_semester(a, b));
+    semesters.dedup();
+
+    let mut cumulative = Average::default();
+    semesters
+        .into_iter()
+        .map(|semester| {
+            let mut term = Average::default();
+            for record in records
+                .iter()
+                .filter(|record| record.semester() == semester)
+            {
+                if let Some(point) = scheme.counted_point(record) {
+                    term.add(point, record.credit);
+                    cumulative.add(point, record.credit);
+                } else if scheme.counts_credit(record) {
+                    term.add_credit(record.credit);
+                    cumulative.add_credit(record.credit);
+                }
+            }
+            SemesterSummary {
+                semester,
+                term,
+                cumulative,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scheme(scale: GpaScale) -> GpaScheme {
+        GpaScheme {
+            scale,
+            major_only: false,
+            grade_mapping: default_grade_mapping(),
+            custom_scale: default_custom_scale(),
+            major_courses: Vec::new(),
+            retake_policy: RetakePolicy::Latest,
+            course_groups: default_course_groups(),
+        }
+    }
+
+    fn record(code: &str, grade: &str, credit: f64, gpa: f64, semester: &str) -> GradeRecord {
+        let (year, term) = semester.split_at(9);
+        GradeRecord {
+            course_code: code.to_string(),
+            name: code.to_string(),
+            grade: grade.to_string(),
+            score: grade.parse().ok(),
+            credit,
+            gpa,
+            year: year.to_string(),
+            term: term.to_string(),
+            attempt: Attempt::Normal,
+            superseded: false,
+        }
+    }
+
+    /// 以第一个学年与最后一个学期为当前学期
+    fn calculator(years: &[&str], terms: &[&str]) -> GpaCalculator {
+        let current = match (years.first(), terms.last()) {
+            (Some(year), Some(term)) => Some((year.to_string(), term.to_string())),
+            _ => None,
+        };
+        GpaCalculator::new(
+            years.iter().map(|year| year.to_string()).collect(),
+            terms.iter().map(|term| term.to_string()).collect(),
+            current,
+            scheme(GpaScale::Five),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_average(average: &Average, weighted: f64, credit: f64, total_credit: f64) {
+        assert_close(average.weighted, weighted);
+        assert_close(average.credit, credit);
+        assert_close(average.total_credit, total_credit);
+    }
+
+    #[test]
+    fn from_json_parses_string_and_numeric_credit() {
+        let string_credit = GradeRecord::from_json(&json!({
+            "kcdm": "MATH1136G",
+            "kcmc": "微积分（甲）Ⅰ",
+            "cj": " 95 ",
+            "xf": " 5.0",
+            "jd": 5.0,
+            "xn": "2023-2024",
+            "xq": "秋冬",
+            "ksxz": "正常考试",
+        }))
+        .unwrap();
+        assert_eq!(string_credit.course_code, "MATH1136G");
+        assert_eq!(string_credit.grade, "95");
+        assert_eq!(string_credit.score, Some(95.0));
+        assert_close(string_credit.credit, 5.0);
+        assert_eq!(string_credit.semester(), "2023-2024秋冬");
+        assert!(string_credit.attempt == Attempt::Normal);
+
+        let numeric_credit = GradeRecord::from_json(&json!({
+            "xkkh": "(2024-2025-1)-CS1001G-0094444-1",
+            "kcmc": "程序设计基础",
+            "cj": "优秀",
+            "xf": 2.5,
+            "jd": 4.8,
+            "xn": "2024-2025",
+            "xq": "秋",
+            "ksxz": "重修",
+        }))
+        .unwrap();
+        assert_eq!(numeric_credit.course_code, "CS1001G");
+        assert_eq!(numeric_credit.score, None);
+        assert_close(numeric_credit.credit, 2.5);
+        assert!(numeric_credit.attempt == Attempt::Retake);
+    }
+
+    #[test]
+    fn from_json_rejects_missing_fields() {
+        let full = json!({
+            "kcdm": "PHIL1001G",
+            "kcmc": "哲学导论",
+            "cj": "80",
+            "xf": "2.0",
+            "jd": 3.5,
+            "xn": "2023-2024",
+            "xq": "春夏",
+        });
+        let parsed = GradeRecord::from_json(&full).unwrap();
+        assert!(parsed.attempt == Attempt::Normal);
+        for field in ["kcmc", "cj", "xf", "jd", "xn", "xq"] {
+            let mut value = full.clone();
+            value.as_object_mut().unwrap().remove(field);
+            assert!(GradeRecord::from_json(&value).is_none(), "缺少 {field}");
+        }
+        // 课程代码缺失时为空，不影响解析
+        let mut value = full.clone();
+        value.as_object_mut().unwrap().remove("kcdm");
+        assert_eq!(GradeRecord::from_json(&value).unwrap().course_code, "");
+        assert!(GradeRecord::from_json(&json!({ "xf": "abc" })).is_none());
+        assert!(GradeRecord::from_json(&json!("80")).is_none());
+    }
+
+    #[test]
+    fn parse_all_skips_dropped_and_invalid_rows() {
+        let values = vec![
+            json!({
+                "kcdm": "MATH1136G", "kcmc": "微积分", "cj": "弃修",
+                "xf": "5.0", "jd": 0.0, "xn": "2023-2024", "xq": "秋冬",
+            }),
+            json!({
+                "kcdm": "MATH1137G", "kcmc": "线性代数", "cj": "88",
+                "xf": "3.5", "jd": 4.5, "xn": "2023-2024", "xq": "秋冬",
+            }),
+            json!({ "kcmc": "无效记录" }),
+        ];
+        let records = GradeRecord::parse_all(&values);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "线性代数");
+        assert!(GradeRecord::from_json(&values[0]).unwrap().is_dropped());
+    }
+
+    #[test]
+    fn five_point_boundaries() {
+        assert_close(five_point(59.0), 0.0);
+        assert_close(five_point(59.9), 0.0);
+        assert_close(five_point(60.0), 1.5);
+        assert_close(five_point(85.7), 4.0);
+        assert_close(five_point(95.0), 5.0);
+        assert_close(five_point(100.0), 5.0);
+    }
+
+    #[test]
+    fn summarize_sums_overall_year_term_and_groups() {
+        let records = vec![
+            // 大课
+            record("A", "85", 4.0, 4.0, "2023-2024秋冬"),
+            // 中课
+            record("B", "75", 2.0, 3.0, "2024-2025春夏"),
+            // 小课
+            record("C", "95", 1.0, 5.0, "2024-2025秋"),
+            // 通过的合格制课程只计学分
+            record("D", "合格", 1.5, 0.0, "2024-2025冬"),
+            // 未通过的合格制课程不计
+            record("E", "不合格", 1.0, 0.0, "2024-2025冬"),
+        ];
+        let calculator = calculator(&["2024-2025"], &["秋", "冬", "秋冬"]);
+
+        let summary = calculator.summarize(&records, Scope::All);
+        assert_average(&summary.overall, 27.0, 7.0, 8.5);
+        assert_average(&summary.year, 11.0, 3.0, 4.5);
+        assert_average(&summary.term, 5.0, 1.0, 2.5);
+        assert_close(summary.overall.value(), 27.0 / 7.0);
+        let names: Vec<&str> = summary
+            .groups
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["大课", "中课", "小课"]);
+        assert_average(&summary.groups[0].1, 16.0, 4.0, 4.0);
+        assert_average(&summary.groups[1].1, 6.0, 2.0, 2.0);
+        assert_average(&summary.groups[2].1, 5.0, 1.0, 2.5);
+
+        // 分组均绩只统计 scope 范围内的成绩，总均绩等不受影响
+        let summary = calculator.summarize(&records, Scope::Term);
+        assert_average(&summary.overall, 27.0, 7.0, 8.5);
+        assert_average(&summary.groups[0].1, 0.0, 0.0, 0.0);
+        assert_average(&summary.groups[1].1, 0.0, 0.0, 0.0);
+        assert_average(&summary.groups[2].1, 5.0, 1.0, 2.5);
+        assert_close(summary.groups[0].1.value(), 0.0);
+    }
+
+    #[test]
+    fn summarize_skips_superseded_records() {
+        let mut superseded = record("A", "50", 4.0, 0.0, "2023-2024秋冬");
+        superseded.superseded = true;
+        let records = vec![superseded, record("A", "90", 4.0, 4.5, "2024-2025秋冬")];
+        let summary = calculator(&["2024-2025"], &["秋冬"]).summarize(&records, Scope::All);
+        assert_average(&summary.overall, 18.0, 4.0, 4.0);
+    }
+
+    /// 同一课程：第一次不及格、同学期补考、重修取得高分、再次修读
+    fn attempts() -> Vec<GradeRecord> {
+        let mut make_up = record("A", "60", 4.0, 1.5, "2022-2023秋冬");
+        make_up.attempt = Attempt::MakeUp;
+        let mut retake = record("A", "95", 4.0, 5.0, "2023-2024秋冬");
+        retake.attempt = Attempt::Retake;
+        vec![
+            record("A", "85", 4.0, 4.0, "2024-2025秋冬"),
+            retake,
+            record("B", "80", 2.0, 3.5, "2023-2024春夏"),
+            make_up,
+            record("A", "40", 4.0, 0.0, "2022-2023秋冬"),
+        ]
+    }
+
+    fn superseded(records: &[GradeRecord]) -> Vec<bool> {
+        records.iter().map(|record| record.superseded).collect()
+    }
+
+    #[test]
+    fn retake_policy_latest_keeps_last_attempt() {
+        let mut records = attempts();
+        RetakePolicy::Latest.apply(&mut records);
+        assert_eq!(superseded(&records), [false, true, false, true, true]);
+    }
+
+    #[test]
+    fn retake_policy_highest_keeps_best_attempt() {
+        let mut records = attempts();
+        RetakePolicy::Highest.apply(&mut records);
+        assert_eq!(superseded(&records), [true, false, false, true, true]);
+
+        // 绩点相同时取较晚的一次
+        let mut records = vec![
+            record("A", "95", 4.0, 5.0, "2024-2025秋冬"),
+            record("A", "96", 4.0, 5.0, "2023-2024秋冬"),
+        ];
+        RetakePolicy::Highest.apply(&mut records);
+        assert_eq!(superseded(&records), [false, true]);
+    }
+
+    #[test]
+    fn retake_policy_first_keeps_first_attempt() {
+        let mut records = attempts();
+        RetakePolicy::First.apply(&mut records);
+        assert_eq!(superseded(&records), [true, true, false, true, false]);
+    }
+
+    #[test]
+    fn retake_policy_relabels_later_normal_attempts() {
+        let mut records = attempts();
+        RetakePolicy::Latest.apply(&mut records);
+        let attempts: Vec<Attempt> = records.iter().map(|record| record.attempt).collect();
+        assert!(
+            attempts
+                == [
+                    Attempt::Retake,
+                    Attempt::Retake,
+                    Attempt::Normal,
+                    Attempt::MakeUp,
+                    Attempt::Normal,
+                ]
+        );
+        assert_eq!(records[0].remarks(), "重修");
+        assert_eq!(records[1].remarks(), "重修，已被替代");
+    }
+
+    #[test]
+    fn retake_policy_groups_by_name_without_course_code() {
+        let mut first = record("", "50", 2.0, 0.0, "2023-2024春夏");
+        first.name = "体育".to_string();
+        let mut second = record("", "70", 2.0, 2.5, "2024-2025春夏");
+        second.name = "体育".to_string();
+        let mut records = vec![second, first];
+        RetakePolicy::Latest.apply(&mut records);
+        assert_eq!(superseded(&records), [false, true]);
+        assert!(records[0].attempt == Attempt::Retake);
+    }
+
+    #[test]
+    fn retake_policy_sorts_unparseable_semesters_last() {
+        let mut unknown = record("A", "70", 4.0, 2.5, "2023-2024秋冬");
+        unknown.year = "未知".to_string();
+        let mut records = vec![unknown, record("A", "50", 4.0, 0.0, "2023-2024秋冬")];
+        RetakePolicy::Latest.apply(&mut records);
+        assert_eq!(superseded(&records), [false, true]);
+    }
+
+    #[test]
+    fn counts_credit_only_for_passed_pass_fail_grades() {
+        let scheme = scheme(GpaScale::Five);
+        assert!(scheme.counts_credit(&record("A", "合格", 1.0, 0.0, "2024-2025秋")));
+        assert!(scheme.counts_credit(&record("A", "通过", 1.0, 0.0, "2024-2025秋")));
+        assert!(!scheme.counts_credit(&record("A", "不合格", 1.0, 0.0, "2024-2025秋")));
+        assert!(!scheme.counts_credit(&record("A", "不通过", 1.0, 0.0, "2024-2025秋")));
+        // 计绩点的成绩由 counted_point 统计
+        assert!(!scheme.counts_credit(&record("A", "90", 1.0, 4.5, "2024-2025秋")));
+
+        let mut superseded = record("A", "合格", 1.0, 0.0, "2024-2025秋");
+        superseded.superseded = true;
+        assert!(!scheme.counts_credit(&superseded));
+
+        let major_only = GpaScheme {
+            major_only: true,
+            major_courses: vec!["MATH".to_string()],
+            ..scheme
+        };
+        assert!(major_only.counts_credit(&record("MATH1001", "合格", 1.0, 0.0, "2024-2025秋")));
+        assert!(!major_only.counts_credit(&record("PHYS1001", "合格", 1.0, 0.0, "2024-2025秋")));
+    }
+
+    fn pending_course(name: &str, credit: f64, grade: Option<&str>) -> PendingCourse {
+        PendingCourse {
+            name: name.to_string(),
+            credit,
+            grade: grade.map(String::from),
+        }
+    }
+
+    #[test]
+    fn simulate_adds_pending_courses_to_current_term() {
+        let calculator = calculator(&["2024-2025"], &["秋冬"]);
+        let records = vec![
+            record("A", "85", 4.0, 4.0, "2023-2024秋冬"),
+            record("B", "95", 2.0, 5.0, "2024-2025秋冬"),
+        ];
+        let pending = vec![
+            pending_course("C", 2.0, Some("75")),
+            pending_course("D", 1.0, Some("合格")),
+            pending_course("E", 1.0, None),
+        ];
+
+        // 待求解的课程在 unknown_score 为 None 时不计入
+        let summary = calculator.simulate(&records, &pending, None);
+        assert_average(&summary.term, 16.0, 4.0, 5.0);
+        assert_average(&summary.overall, 32.0, 8.0, 9.0);
+
+        let summary = calculator.simulate(&records, &pending, Some(60.0));
+        assert_average(&summary.term, 17.5, 5.0, 6.0);
+        assert_average(&summary.year, 17.5, 5.0, 6.0);
+
+        // 等级制成绩按映射表换算
+        let summary =
+            calculator.simulate(&records, &[pending_course("C", 2.0, Some("优秀"))], None);
+        assert_average(&summary.term, 20.0, 4.0, 4.0);
+    }
+
+    #[test]
+    fn min_required_score_finds_lowest_score() {
+        let calculator = calculator(&["2024-2025"], &["秋冬"]);
+        let records = vec![record("A", "75", 2.0, 3.0, "2024-2025秋冬")];
+        let pending = vec![pending_course("B", 2.0, None)];
+
+        // (3.0 * 2 + x * 2) / 4 >= 3.5 => x >= 4.0，即 85 分
+        let required = calculator.min_required_score(&records, &pending, 3.5, Scope::Term);
+        assert_eq!(required, Some(85.0));
+        let required = calculator.min_required_score(&records, &pending, 1.0, Scope::Term);
+        assert_eq!(required, Some(0.0));
+        let required = calculator.min_required_score(&records, &pending, 4.5, Scope::Term);
+        assert_eq!(required, None);
+    }
+
+    #[test]
+    fn min_required_score_searches_breakpoints() {
+        let records = vec![record("A", "80", 2.0, 3.5, "2024-2025秋冬")];
+        let pending = vec![pending_course("B", 2.0, None)];
+
+        // 百分制：(80 * 2 + x * 2) / 4 >= 83.7 => x >= 87.4
+        let mut calculator = calculator(&["2024-2025"], &["秋冬"]);
+        calculator.scheme.scale = GpaScale::Hundred;
+        let required = calculator.min_required_score(&records, &pending, 83.7, Scope::Term);
+        assert_close(required.unwrap(), 87.4);
+
+        // 自定义绩点表中不是整数分的一档
+        calculator.scheme.scale = GpaScale::Custom;
+        calculator.scheme.custom_scale = vec![
+            ScaleStep {
+                min_score: 84.5,
+                point: 4.0,
+            },
+            ScaleStep {
+                min_score: 60.0,
+                point: 2.0,
+            },
+        ];
+        let required = calculator.min_required_score(&records, &pending, 3.0, Scope::Term);
+        assert_eq!(required, Some(84.5));
+    }
+}
@@ -1,10 +1,19 @@
 pub mod account;
+pub mod cas;
+pub mod chart;
 pub mod check_up;
 pub mod command_async;
 pub mod command_blocking;
 pub mod command_share;
 pub mod completer;
+pub mod export;
+pub mod grade;
+pub mod macros;
 pub mod network;
+pub mod retry;
+pub mod snapshot;
+pub mod table;
+pub mod throttle;
+pub mod update;
 pub mod utils;
-pub mod macros;
-pub mod update;
\ No newline at end of file
+pub mod vault;
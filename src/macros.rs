@@ -185,4 +185,3 @@ macro_rules! gray {
         format!("\x1B[90m{}\x1B[0m", format!($($arg)*))
     }};
 }
-
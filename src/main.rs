@@ -1,11 +1,15 @@
 use anyhow::Result;
 use clap::{ArgGroup, Parser};
 use rustyline::history::FileHistory;
-use rustyline::Editor;
+use rustyline::{Editor, ExternalPrinter};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use zac::completer::GenericHelper;
-use zac::{account, check_up, command_async, command_blocking, completer, network, update, utils};
-use zac::{begin, end, error, success, try_or_log, warning};
+use zac::{
+    account, check_up, command_async, command_blocking, command_share, completer, network, update,
+    utils,
+};
+use zac::{begin, end, error, success, try_or_exit, try_or_log, warning};
 const CMD_NAME: &str = "zac";
 
 const CMD_ABOUT: &str = "zac(zju-assistant-cli) 是一个用于获取或上传雪灾浙大资源的命令行工具。若想了解更多，见 https://github.com/CrazySpottedDove/zac";
@@ -19,13 +23,17 @@ const CMD_ABOUT: &str = "zac(zju-assistant-cli) 是一个用于获取或上传
     group(
         ArgGroup::new("commands")
             .required(false)
-            .args(&["fetch", "submit", "upgrade", "which","task","grade","config"])
+            .args(&["fetch", "submit", "upgrade", "which","task","course","announce","grade","config"])
     )
 )]
 struct Cli {
     /// 拉取课件
     #[arg(short, long)]
     fetch: bool,
+    #[command(flatten)]
+    fetch_args: command_share::FetchArgs,
+    #[command(flatten)]
+    grade_args: command_share::GradeArgs,
     /// 提交作业
     #[arg(short, long)]
     submit: bool,
@@ -38,6 +46,12 @@ struct Cli {
     /// 查看作业
     #[arg(short, long)]
     task: bool,
+    /// 查看课程公告
+    #[arg(long)]
+    announce: bool,
+    /// 浏览课程活动并下载单个文件，课程名支持部分匹配
+    #[arg(long, value_name = "课程名", num_args = 0..=1, default_missing_value = "")]
+    course: Option<String>,
     /// 查看所有成绩
     #[arg(long)]
     grade: bool,
@@ -47,7 +61,13 @@ struct Cli {
     /// 持续查询本学期成绩
     #[arg(short, long)]
     polling: bool,
-    /// 配置[用户，存储目录，是否 ppt 转 pdf，是否下载 mp4 文件]
+    /// 模拟未出分课程的均绩
+    #[arg(long)]
+    simulate: bool,
+    /// 目标均绩，求未出分课程所需的最低分数
+    #[arg(long, value_name = "绩点", requires = "simulate")]
+    target: Option<f64>,
+    /// 配置[用户，存储目录，是否 ppt 转 pdf，是否下载 mp4 文件，并发与限速]
     #[arg(short, long)]
     config: bool,
     /// 执行更新
@@ -56,8 +76,10 @@ struct Cli {
 }
 
 fn guarantee_login_and_check_new_version(
+    session: &network::Session,
+    account: &mut account::Account,
     login_ready: &mut bool,
-    pre_login_thread_wrapper: &mut Option<JoinHandle<()>>,
+    pre_login_thread_wrapper: &mut Option<command_async::PreLoginHandle>,
     check_new_version_thread: &mut Option<JoinHandle<bool>>,
 ) {
     if !*login_ready {
@@ -67,7 +89,13 @@ fn guarantee_login_and_check_new_version(
         }
         begin!("登录");
         let handle = pre_login_thread_wrapper.take().expect("线程句柄不可用");
-        handle.join().unwrap();
+        // 预登录失败(如密码已修改)时在前台重新登录，必要时更新密码
+        if let Err(e) = handle.thread.join().unwrap() {
+            println!();
+            error!("登录：{e}");
+            try_or_exit!(command_share::login(session, account), "登录");
+            command_async::notify_unread_announcements(session.clone(), handle.printer);
+        }
         end!("登录");
         *login_ready = true;
     }
@@ -79,81 +107,159 @@ fn single_iterative_term(
     account: &mut account::Account,
     settings: &mut utils::Settings,
     login_ready: &mut bool,
-    pre_login_thread_wrapper: &mut Option<JoinHandle<()>>,
+    pre_login_thread_wrapper: &mut Option<command_async::PreLoginHandle>,
     check_new_version_thread_wrapper: &mut Option<JoinHandle<bool>>,
 ) -> Result<bool> {
     match rl.readline(&format!("{} > ", CMD_NAME)) {
-        Ok(input) => match input.as_str() {
-            "fetch" | "f" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::fetch(settings, session)?;
-            }
-            "submit" | "s" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::submit(session)?;
-            }
-            "upgrade" | "u" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::upgrade(session)?;
-            }
-            "which" | "w" => {
-                command_async::which(session)?;
-            }
-            "task" | "t" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::task(session)?;
-            }
-            "grade" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::grade(session, &account.default)?;
-            }
-            "g" => {
-                guarantee_login_and_check_new_version(
-                    login_ready,
-                    pre_login_thread_wrapper,
-                    check_new_version_thread_wrapper,
-                );
-                command_async::g(session, &account.default)?;
-            }
-            "p" | "polling" => {
-                command_async::polling(session, &account.default)?;
-            }
-            "config" | "c" => {
-                command_async::config(settings, account, session)?;
-            }
-            "help" | "h" => {
-                command_async::help();
-            }
-            "update" => {
-                update::update()?;
-            }
-            "v" | "version" => {
-                success!("v{}", env!("CARGO_PKG_VERSION"));
-            }
-            _ => {
-                warning!("无效命令，请重新输入");
+        Ok(input) => {
+            let mut words = input.split_whitespace();
+            let cmd = words.next().unwrap_or_default();
+            let args: Vec<&str> = words.collect();
+            match cmd {
+                "fetch" | "f" => {
+                    let Some(fetch_args) = command_async::parse_args("fetch", &args) else {
+                        return Ok(false);
+                    };
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::fetch(settings, session, &fetch_args)?;
+                }
+                "submit" | "s" => {
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::submit(session)?;
+                }
+                "upgrade" | "u" => {
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::upgrade(session)?;
+                }
+                "which" | "w" => {
+                    command_async::which(session)?;
+                }
+                "task" | "t" => {
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::task(session)?;
+                }
+                "course" => {
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::course(settings, session, &args.join(" "))?;
+                }
+                "announce" => {
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::announce(session)?;
+                }
+                "grade" => {
+                    // grade export --format csv 等价于 grade --export csv
+                    let (args, exporting) = match args.split_first() {
+                        Some((&"export", rest)) => (rest.to_vec(), true),
+                        _ => (args, false),
+                    };
+                    let Some(grade_args) =
+                        command_async::parse_args::<command_share::GradeArgs>("grade", &args)
+                    else {
+                        return Ok(false);
+                    };
+                    if exporting && grade_args.export.is_none() {
+                        warning!("请用 --format 指定导出格式：csv | json | md | html");
+                        return Ok(false);
+                    }
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::grade(settings, session, &account.default, &grade_args)?;
+                }
+                "g" => {
+                    let Some(grade_args) = command_async::parse_args("g", &args) else {
+                        return Ok(false);
+                    };
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::g(settings, session, &account.default, &grade_args)?;
+                }
+                "gpa" => {
+                    let Some((&"simulate", args)) = args.split_first() else {
+                        warning!("用法：gpa simulate [--target 目标均绩]");
+                        return Ok(false);
+                    };
+                    let Some(simulate_args) = command_async::parse_args("gpa simulate", args)
+                    else {
+                        return Ok(false);
+                    };
+                    guarantee_login_and_check_new_version(
+                        session,
+                        account,
+                        login_ready,
+                        pre_login_thread_wrapper,
+                        check_new_version_thread_wrapper,
+                    );
+                    command_async::simulate(settings, session, &account.default, &simulate_args)?;
+                }
+                "p" | "polling" => {
+                    let Some(grade_args) = command_async::parse_args("polling", &args) else {
+                        return Ok(false);
+                    };
+                    command_async::polling(settings, session, &account.default, &grade_args)?;
+                }
+                "config" | "c" => {
+                    command_async::config(settings, account, session)?;
+                }
+                "help" | "h" => {
+                    command_async::help();
+                }
+                "update" => {
+                    update::update()?;
+                }
+                "v" | "version" => {
+                    success!("v{}", env!("CARGO_PKG_VERSION"));
+                }
+                _ => {
+                    warning!("无效命令，请重新输入");
+                }
             }
-        },
+        }
         Err(rustyline::error::ReadlineError::Interrupted)
         | Err(rustyline::error::ReadlineError::Eof) => {
             return Ok(true);
@@ -166,38 +272,58 @@ fn single_iterative_term(
 }
 
 fn main() {
+    try_or_log!(utils::install_ctrlc_handler(), "安装 Ctrl+C 处理");
     let (mut settings, mut account, session) = check_up::all_up();
     let cli = Cli::parse();
 
     if cli.fetch {
-        command_blocking::fetch(&account.default, &settings, &session);
+        command_blocking::fetch(&mut account, &settings, &session, &cli.fetch_args);
     } else if cli.submit {
-        command_blocking::submit(&session, &account.default);
+        command_blocking::submit(&session, &mut account);
     } else if cli.upgrade {
-        command_blocking::upgrade(&session, &account.default);
+        command_blocking::upgrade(&session, &mut account);
     } else if cli.which {
         command_blocking::which(&session);
     } else if cli.task {
-        command_blocking::task(&session, &account.default);
+        command_blocking::task(&session, &mut account);
+    } else if cli.announce {
+        command_blocking::announce(&session, &mut account);
+    } else if let Some(keyword) = &cli.course {
+        command_blocking::course(&settings, &session, &mut account, keyword);
     } else if cli.config {
         command_blocking::config(&mut settings, &mut account, &session);
     } else if cli.grade {
-        command_blocking::grade(&session, &account.default);
+        command_blocking::grade(&settings, &session, &mut account, &cli.grade_args);
     } else if cli.g {
-        command_blocking::g(&session, &account.default);
+        command_blocking::g(&settings, &session, &mut account, &cli.grade_args);
     } else if cli.polling {
-        command_blocking::polling(&session, &account.default);
+        command_blocking::polling(&settings, &session, &mut account, &cli.grade_args);
+    } else if cli.simulate {
+        let args = command_share::SimulateArgs {
+            scale: cli.grade_args.scale,
+            major: cli.grade_args.major,
+            target: cli.target,
+        };
+        command_blocking::simulate(&settings, &session, &mut account, &args);
     } else if cli.update {
         try_or_log!(update::update(), "更新");
     } else {
+        let mut rl = completer::build_generic_editor(completer::CommandType::MainCommand);
+        // 后台消息经由 rustyline 输出，避免打乱正在输入的命令行
+        let printer: command_async::Printer = match rl.create_external_printer() {
+            Ok(mut external_printer) => Arc::new(Mutex::new(move |msg: String| {
+                let _ = external_printer.print(msg);
+            })),
+            Err(_) => Arc::new(Mutex::new(|msg: String| print!("{msg}"))),
+        };
         let mut pre_login_thread_wrapper = Some(command_async::pre_login(
             account.default.clone(),
             session.clone(),
+            printer,
         ));
         let mut new_version_check_thread_wrapper = Some(update::check_update());
         let mut login_ready = false;
         command_async::help();
-        let mut rl = completer::build_generic_editor(completer::CommandType::MainCommand);
         loop {
             match single_iterative_term(
                 &mut rl,
@@ -1,16 +1,23 @@
+use crate::cas;
+use crate::chart;
+use crate::export;
+use crate::grade::{self, Average, GpaCalculator, GpaScale, GpaScheme, GradeRecord, Scope};
+use crate::retry::{self, RetryPolicy};
+use crate::snapshot::{GradeChange, GradeSnapshot, SnapshotEntry};
+use crate::table::{create_table, display_width, pad};
+use crate::throttle::Throttle;
 use crate::{
     account, begin, end, error, success, try_or_exit, try_or_throw, utils, waiting, warning,
 };
-use crate::{blue, gray, purple};
+use crate::{blue, gray, green, purple, red, yellow};
 
 use ::serde::{Deserialize, Serialize};
 use anyhow::{anyhow, Result};
 use cookie_store::CookieStore;
-use num::ToPrimitive;
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
 use reqwest::blocking::multipart;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
 use reqwest::header::{HeaderMap, USER_AGENT};
 use reqwest_cookie_store::CookieStoreMutex;
 use serde_json::json;
@@ -20,16 +27,27 @@ use std::fs::{self, File};
 use std::io::{stdout, Read};
 use std::ops::Deref;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 
-const LOGIN_URL: &str = "https://zjuam.zju.edu.cn/cas/login";
-const PUBKEY_URL: &str = "https://zjuam.zju.edu.cn/cas/v2/getPubKey";
 const HOME_URL: &str = "https://courses.zju.edu.cn";
 const GRADE_SERVICE_URL: &str = "http://appservice.zju.edu.cn/zdjw/cjcx/cjcxjg";
 const GRADE_URL: &str = "http://appservice.zju.edu.cn/zju-smartcampus/zdydjw/api/kkqk_cxXscjxx";
 const POST_URL: &str = "https://courses.zju.edu.cn/api/uploads";
 
+/// 学在浙大
+const COURSES_SERVICE: cas::Service = cas::Service {
+    name: "雪灾浙大主页",
+    url: HOME_URL,
+};
+/// 成绩查询
+const GRADE_SERVICE: cas::Service = cas::Service {
+    name: "成绩查询主页",
+    url: GRADE_SERVICE_URL,
+};
+/// 登录时需要建立会话的服务
+const SERVICES: &[cas::Service] = &[COURSES_SERVICE, GRADE_SERVICE];
+
 use {
     indicatif::{MultiProgress, ProgressBar, ProgressStyle},
     std::io::Write,
@@ -38,54 +56,97 @@ use {
 #[cfg(debug_assertions)]
 use crate::process;
 
-fn rsa_no_padding(src: &str, modulus: &str, exponent: &str) -> String {
-    let m = num::BigUint::parse_bytes(modulus.as_bytes(), 16).unwrap();
-    let e = num::BigUint::parse_bytes(exponent.as_bytes(), 16).unwrap();
-
-    let input_nr = num::BigUint::from_bytes_be(src.as_bytes());
-
-    let crypt_nr = input_nr.modpow(&e, &m);
-
-    crypt_nr
-        .to_bytes_be()
-        .iter()
-        .map(|byte| format!("{:02x}", byte))
-        .collect()
-}
-
 /// 会话状态
 struct State {
     /// 共享 cookie 状态
     cookie_store: Arc<CookieStoreMutex>,
-    /// 本地 cookie 文件路径
-    path_cookies: PathBuf,
+    /// 当前账号的数据文件路径
+    paths: RwLock<utils::AccountPaths>,
+    /// 上次保存 cookie 的时间
+    last_saved: Mutex<Instant>,
 }
 
+/// 定期保存 cookie 的间隔
+const COOKIE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
 impl State {
     /// 建立新的 state
     ///
     /// 使用可能已经存在的本地 cookie 文件。若没有，会自动创建
-    pub fn try_new(path_cookies: PathBuf) -> Result<State> {
+    pub fn try_new(paths: utils::AccountPaths) -> Result<State> {
+        let cookie_store = Self::load_cookie_store(&paths.cookies)?;
+        let cookie_store = Arc::new(CookieStoreMutex::new(cookie_store));
+        Ok(State {
+            cookie_store,
+            paths: RwLock::new(paths),
+            last_saved: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// 读取本地 cookie 文件，若没有则创建
+    fn load_cookie_store(path_cookies: &PathBuf) -> Result<CookieStore> {
         #[allow(deprecated)]
-        let cookie_store = match File::open(&path_cookies) {
+        let cookie_store = match File::open(path_cookies) {
             Ok(file) => CookieStore::load_json(std::io::BufReader::new(file)).unwrap_or_default(),
             Err(_) => {
-                File::create(&path_cookies)?;
+                File::create(path_cookies)?;
                 CookieStore::default()
             }
         };
-        let cookie_store = Arc::new(CookieStoreMutex::new(cookie_store));
-        Ok(State {
-            cookie_store,
-            path_cookies,
-        })
+        Ok(cookie_store)
+    }
+
+    /// 切换到另一个账号的数据文件：保存当前 cookie，再载入新账号的 cookie
+    fn switch(&self, paths: utils::AccountPaths) -> Result<()> {
+        // 旧账号被删除时其数据目录已不存在，无需再保存 cookie
+        let old_dir_exists = self
+            .paths
+            .read()
+            .unwrap()
+            .cookies
+            .parent()
+            .is_some_and(|dir| dir.exists());
+        if old_dir_exists {
+            self.save()?;
+        }
+        let cookie_store = Self::load_cookie_store(&paths.cookies)?;
+        *self.cookie_store.lock().unwrap() = cookie_store;
+        *self.paths.write().unwrap() = paths;
+        Ok(())
+    }
+
+    /// 保存 cookie 到本地，先写临时文件再重命名，避免写坏 cookie 文件
+    pub fn save(&self) -> Result<()> {
+        let mut json = Vec::new();
+        {
+            let store = self.cookie_store.lock().unwrap();
+            #[allow(deprecated)]
+            store
+                .save_json(&mut json)
+                .map_err(|e| anyhow!("序列化 cookie：{e}"))?;
+        }
+        let path_cookies = self.paths.read().unwrap().cookies.clone();
+        utils::atomic_write_private(&path_cookies, json)?;
+        *self.last_saved.lock().unwrap() = Instant::now();
+        Ok(())
+    }
+
+    /// 距上次保存超过 COOKIE_SAVE_INTERVAL 时保存 cookie
+    fn save_if_stale(&self) {
+        if self.last_saved.lock().unwrap().elapsed() < COOKIE_SAVE_INTERVAL {
+            return;
+        }
+        if let Err(e) = self.save() {
+            error!("保存 cookie：{e}");
+        }
     }
 
     /// 清除当前 cookie 和本地 cookie
     pub fn clear_cookie(&self) -> Result<()> {
         self.cookie_store.lock().unwrap().clear();
-        if self.path_cookies.exists() {
-            fs::remove_file(&self.path_cookies)?;
+        let path_cookies = self.paths.read().unwrap().cookies.clone();
+        if path_cookies.exists() {
+            fs::remove_file(&path_cookies)?;
         }
         Ok(())
     }
@@ -93,31 +154,8 @@ impl State {
 
 impl Drop for State {
     fn drop(&mut self) {
-        let mut file = match fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.path_cookies)
-        {
-            Ok(f) => f,
-            Err(e) => {
-                error!(
-                    "open {} for write failed. error: {}",
-                    self.path_cookies.display(),
-                    e
-                );
-                return;
-            }
-        };
-
-        let store = self.cookie_store.lock().unwrap();
-        #[allow(deprecated)]
-        if let Err(e) = store.save_json(&mut file) {
-            error!(
-                "save cookies to path {} failed. error: {}",
-                &self.path_cookies.display(),
-                e
-            );
+        if let Err(e) = self.save() {
+            error!("save cookies failed. error: {}", e);
         }
     }
 }
@@ -125,44 +163,59 @@ impl Drop for State {
 #[derive(Clone)]
 /// 网络会话
 ///
-/// 自动管理会话 cookie 并在销毁时保存 cookie 到本地
+/// 自动管理会话 cookie，登录后、请求过程中定期以及销毁时保存 cookie 到本地
 pub struct Session {
-    #[allow(dead_code)] // just make clippy happy
     state: Arc<State>,
     client: Client,
-    path_courses: PathBuf,
-    path_active_courses: PathBuf,
-    path_selected_courses: PathBuf,
-    path_activity_upload_record: PathBuf,
-    path_active_semesters: PathBuf,
+    /// 与 client 共享 cookie 但不跟随重定向，用于申请服务票据
+    ticket_client: Client,
+    /// 网络限流器
+    throttle: Arc<Throttle>,
+    /// 请求的重试策略
+    retry: Arc<RetryPolicy>,
+    /// 登录状态，用于会话过期后自动重新登录
+    auth: Arc<Mutex<AuthState>>,
+    /// 自动重新登录的闸门，保证同一时间只有一个线程在重新登录
+    relogin: Arc<Mutex<()>>,
+}
+
+/// 会话的登录状态
+#[derive(Default)]
+struct AuthState {
+    /// 最近一次登录的账号
+    account: Option<account::AccountData>,
+    /// 登录代数，每次自动重新登录后加一
+    generation: u64,
 }
 
 impl Session {
     /// 建立新的会话
-    pub fn try_new(
-        path_cookies: PathBuf,
-        path_courses: PathBuf,
-        path_active_courses: PathBuf,
-        path_selected_courses: PathBuf,
-        path_activity_upload_record: PathBuf,
-        path_active_semesters: PathBuf,
-    ) -> Result<Session> {
-        let state = State::try_new(path_cookies)?;
+    pub fn try_new(paths: utils::AccountPaths) -> Result<Session> {
+        let state = State::try_new(paths)?;
         let state = Arc::new(state);
+        // Ctrl+C 直接退出时不会执行 State 的 drop，需要单独保存 cookie
+        let weak_state = Arc::downgrade(&state);
+        utils::on_exit(move || {
+            if let Some(state) = weak_state.upgrade() {
+                let _ = state.save();
+            }
+        });
         let mut headers = HeaderMap::new();
-        headers.insert(
-            USER_AGENT,
-            "Mozilla/5.0 (X11; Linux x86_64; rv:88.0) Gecko/20100101 Firefox/88.0"
-                .parse()
-                .unwrap(),
-        );
+        headers.insert(USER_AGENT, cas::BROWSER_USER_AGENT.parse().unwrap());
 
         let client = Client::builder()
             .cookie_provider(state.cookie_store.clone())
-            .default_headers(headers)
+            .default_headers(headers.clone())
             .timeout(std::time::Duration::from_secs(1200))
             .no_proxy()
             .build()?;
+        let ticket_client = Client::builder()
+            .cookie_provider(state.cookie_store.clone())
+            .default_headers(headers)
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(std::time::Duration::from_secs(30))
+            .no_proxy()
+            .build()?;
 
         #[cfg(debug_assertions)]
         success!("建立会话");
@@ -170,127 +223,233 @@ impl Session {
         Ok(Session {
             state,
             client,
-            path_courses,
-            path_active_courses,
-            path_selected_courses,
-            path_activity_upload_record,
-            path_active_semesters,
+            ticket_client,
+            throttle: Arc::new(Throttle::default()),
+            retry: Arc::new(RetryPolicy::default()),
+            auth: Arc::new(Mutex::new(AuthState::default())),
+            relogin: Arc::new(Mutex::new(())),
         })
     }
 
-    fn login_core(&self, account: &account::AccountData) -> Result<()> {
-        for retry in 1..=utils::MAX_RETRIES {
-            let (execution, (modulus, exponent)) = rayon::join(
-                || {
-                    let res = try_or_exit!(self.client.get(LOGIN_URL).send(), "连接登录页");
-                    let text = res.text().unwrap();
-                    let re = regex::Regex::new(
-                        r#"<input type="hidden" name="execution" value="(.*?)" />"#,
-                    )
-                    .unwrap();
-                    let execution = re
-                        .captures(&text)
-                        .and_then(|cap| cap.get(1).map(|m| m.as_str()))
-                        .ok_or(anyhow!("Execution value not found"))
-                        .unwrap()
-                        .to_string();
-                    execution
-                },
-                || {
-                    let res = try_or_exit!(self.client.get(PUBKEY_URL).send(), "获取公钥");
-                    let json: Value = try_or_exit!(res.json(), "解析公钥");
-                    let modulus = json["modulus"]
-                        .as_str()
-                        .ok_or(anyhow!("Modulus not found"))
-                        .unwrap()
-                        .to_string();
-                    let exponent = json["exponent"]
-                        .as_str()
-                        .ok_or(anyhow!("Exponent not found"))
-                        .unwrap()
-                        .to_string();
-                    (modulus, exponent)
-                },
-            );
+    /// 当前账号的数据文件路径
+    fn paths(&self) -> RwLockReadGuard<'_, utils::AccountPaths> {
+        self.state.paths.read().unwrap()
+    }
 
-            let rsapwd = rsa_no_padding(&account.password, &modulus, &exponent);
+    /// 切换到另一个账号，保留各账号的 cookie、课程与下载记录!
+    pub fn switch_account(
+        &self,
+        paths: utils::AccountPaths,
+        account: &account::AccountData,
+    ) -> Result<()> {
+        self.state.switch(paths)?;
+        let mut auth = self.auth.lock().unwrap();
+        auth.account = Some(account.clone());
+        auth.generation += 1;
+        Ok(())
+    }
 
-            let params = [
-                ("username", account.stuid.as_str()),
-                ("password", &rsapwd),
-                ("execution", &execution),
-                ("_eventId", "submit"),
-                ("authcode", ""),
-                ("rememberMe", "true"),
-            ];
-            let res = try_or_throw!(self.client.post(LOGIN_URL).form(&params).send(), "提交登录");
+    /// 按设置更新并发、带宽与请求频率限制
+    pub fn apply_settings(&self, settings: &utils::Settings) {
+        self.throttle.configure(
+            settings.requests_per_second,
+            settings.bandwidth_limit.saturating_mul(1024),
+            settings.max_parallel_requests,
+        );
+        self.retry.set_attempts(settings.max_retries);
+    }
 
-            #[cfg(debug_assertions)]
-            println!("{:?}", res);
+    /// 发送请求
+    ///
+    /// 若会话已过期(被重定向到统一身份认证登录页或返回 401)，用记住的账号重新登录一次并重发请求
+    fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let generation = self.auth.lock().unwrap().generation;
+        let replay = request.try_clone();
+        let res = self.send_request(&client, request)?;
+        if !is_session_expired(&res) {
+            return Ok(res);
+        }
+        match replay {
+            Some(replay) if self.login_again(generation) => self.send_request(&client, replay),
+            _ => Ok(res),
+        }
+    }
 
-            if res
-                .url()
-                .to_string()
-                .contains("https://zjuam.zju.edu.cn/cas/login")
-            {
-                if retry == utils::MAX_RETRIES {
-                    return Err(anyhow!("请检查学号-密码正确性及你的网络连接状态"));
+    /// 发送请求，不检查会话是否过期，供登录流程使用
+    fn send_raw(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let (client, request) = request.build_split();
+        self.send_request(&client, request?)
+    }
+
+    /// 超时、连接失败、5xx 与 429 按重试策略退避后重试；请求体无法复制(如上传文件)时不重试
+    fn send_request(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        let attempts = self.retry.attempts();
+        let mut attempt = 1;
+        loop {
+            let Some(cloned) = request.try_clone().filter(|_| attempt < attempts) else {
+                return self.execute(client, request);
+            };
+            match self.execute(client, cloned) {
+                Ok(res) if !retry::is_retryable_status(request.method(), res.status()) => {
+                    return Ok(res)
                 }
+                Err(e) if !retry::is_retryable_error(request.method(), &e) => return Err(e),
                 #[cfg(debug_assertions)]
-                warning!("retry {}/{}: 登录失败", retry, utils::MAX_RETRIES);
-                continue;
+                Ok(res) => warning!(
+                    "retry {attempt}/{attempts}: {} 返回 {}",
+                    request.url(),
+                    res.status()
+                ),
+                #[cfg(debug_assertions)]
+                Err(e) => warning!(
+                    "retry {attempt}/{attempts}: {} 请求失败: {e}",
+                    request.url()
+                ),
+                #[cfg(not(debug_assertions))]
+                _ => {}
             }
+            std::thread::sleep(self.retry.delay(attempt));
+            attempt += 1;
+        }
+    }
 
-            rayon::join(
-                || {
-                    try_or_exit!(self.client.get(HOME_URL).send(), "连接雪灾浙大主页");
-                },
-                || {
-                    try_or_exit!(
-                        self.client.get(GRADE_SERVICE_URL).send(),
-                        "连接成绩查询主页"
-                    );
-                },
-            );
+    /// 按主机限制请求频率后执行请求，并定期保存 cookie
+    fn execute(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        if let Some(host) = request.url().host_str() {
+            self.throttle.wait_for_host(host);
+        }
+        let res = client.execute(request);
+        self.state.save_if_stale();
+        res
+    }
 
-            return Ok(());
+    /// 建立并行 API 请求的线程池，线程数受 max_parallel_requests 限制
+    fn api_pool(&self, num: usize) -> Result<rayon::ThreadPool> {
+        Ok(ThreadPoolBuilder::new()
+            .num_threads(self.throttle.parallel_requests(num))
+            .build()?)
+    }
+
+    /// 会话过期后重新登录，返回之后是否值得重发请求
+    ///
+    /// generation 为发出请求时的登录代数；若其间已有其他线程重新登录，则不再重复登录
+    ///
+    /// 重新登录期间只持有 relogin 闸门而不持有 auth，其他线程的请求不会因读取登录代数而阻塞；
+    /// 登录与授权全程依次进行，不调用 rayon，避免在等待时窃取到同样需要重新登录的任务而自锁；
+    /// 失败时只报告错误，不会退出程序
+    fn login_again(&self, generation: u64) -> bool {
+        let _relogin = self.relogin.lock().unwrap();
+        let account = {
+            let auth = self.auth.lock().unwrap();
+            if auth.generation != generation {
+                return true;
+            }
+            let Some(account) = auth.account.clone() else {
+                return false;
+            };
+            account
+        };
+        warning!("会话已过期，重新登录");
+        match self.login_core(&account, false) {
+            Ok(()) => {
+                self.auth.lock().unwrap().generation += 1;
+                true
+            }
+            Err(e) => {
+                error!("重新登录：{e}");
+                false
+            }
+        }
+    }
+
+    /// 记住登录的账号，用于会话过期后自动重新登录
+    fn remember_account(&self, account: &account::AccountData) {
+        self.auth.lock().unwrap().account = Some(account.clone());
+    }
+
+    /// 统一身份认证客户端，请求经过限流与重试
+    fn with_cas<T>(&self, f: impl FnOnce(&cas::CasClient) -> T) -> T {
+        let send = |request: RequestBuilder| self.send_raw(request);
+        f(&cas::CasClient::new(
+            &self.client,
+            &self.ticket_client,
+            &send,
+        ))
+    }
+
+    /// 为任意接入统一身份认证的服务申请服务票据，供新的后端使用
+    pub fn service_ticket(&self, service_url: &str) -> Result<String> {
+        Ok(self.with_cas(|cas| cas.service_ticket(service_url))?)
+    }
+
+    /// 登录统一身份认证并建立各服务一侧的会话，parallel 为 false 时全程依次请求、不使用 rayon
+    fn login_core(&self, account: &account::AccountData, parallel: bool) -> Result<()> {
+        self.with_cas(|cas| -> Result<()> {
+            // 只重试暂时性的失败，密码错误等情况立即报告
+            for retry in 1..=utils::MAX_RETRIES {
+                match cas.login(account, parallel) {
+                    Ok(()) => break,
+                    Err(e) if retry == utils::MAX_RETRIES || !e.is_transient() => {
+                        return Err(e.into())
+                    }
+                    Err(_e) => {
+                        #[cfg(debug_assertions)]
+                        warning!("retry {}/{}: 登录失败：{_e}", retry, utils::MAX_RETRIES);
+                        std::thread::sleep(self.retry.delay(retry));
+                    }
+                }
+            }
+
+            // 建立各服务一侧的会话
+            let authorize = |service: &cas::Service| -> Result<()> {
+                cas.authorize(service)
+                    .map_err(|e| anyhow!("连接{}：{e}", service.name))?;
+                Ok(())
+            };
+            if parallel {
+                SERVICES.par_iter().try_for_each(authorize)
+            } else {
+                SERVICES.iter().try_for_each(authorize)
+            }
+        })?;
+
+        if let Err(e) = self.state.save() {
+            error!("保存 cookie：{e}");
         }
         Ok(())
     }
 
     /// 登录，使用本地 cookie
+    ///
+    /// 所有服务的会话都仍然有效时无需重新登录
     pub fn login(&self, account: &account::AccountData) -> Result<()> {
-        let (zcourse_query_wrapper, zgrade_query_wrapper) = rayon::join(
-            || {
-                let res = try_or_exit!(self.client.get(HOME_URL).send(), "连接雪灾浙大主页");
-                res.url().query().map(|q| q.to_owned())
-            },
-            || {
-                let res = try_or_exit!(
-                    self.client.get(GRADE_SERVICE_URL).send(),
-                    "连接成绩查询主页"
-                );
-                res.url().query().map(|q| q.to_owned())
-            },
-        );
-        if zcourse_query_wrapper.is_none() && zgrade_query_wrapper.is_none() {
+        self.remember_account(account);
+        let authorized = self.with_cas(|cas| {
+            SERVICES.par_iter().all(|service| {
+                try_or_exit!(cas.is_authorized(service), format!("连接{}", service.name))
+            })
+        });
+        if authorized {
             return Ok(());
         }
-        self.login_core(account)
+        self.login_core(account, true)
     }
 
     /// 清除本地 cookie 并重新登录
     pub fn relogin(&self, account: &account::AccountData) -> Result<()> {
+        self.remember_account(account);
         try_or_throw!(self.state.clear_cookie(), "清除 cookie");
-        self.login_core(account)
+        self.login_core(account, true)
     }
 
     /// 获取学期映射表(id -> name)
     pub fn get_semester_map_and_active_semester(&self) -> Result<(HashMap<u64, String>, String)> {
-        let res = self
-            .client
-            .get("https://courses.zju.edu.cn/api/my-semesters?")
-            .send()?;
+        let res = self.send(
+            self.client
+                .get("https://courses.zju.edu.cn/api/my-semesters?"),
+        )?;
 
         let json: Value = res.json()?;
         let mut active_semester = String::new();
@@ -315,7 +474,7 @@ impl Session {
 
     /// 获取课程列表
     pub fn get_course_list(&self) -> Result<Vec<Course>> {
-        let res = self.client.get("https://courses.zju.edu.cn/api/my-courses?conditions=%7B%22status%22:%5B%22ongoing%22,%22notStarted%22%5D,%22keyword%22:%22%22,%22classify_type%22:%22recently_started%22,%22display_studio_list%22:false%7D&fields=id,name,semester_id&page=1&page_size=1000").send()?;
+        let res = self.send(self.client.get("https://courses.zju.edu.cn/api/my-courses?conditions=%7B%22status%22:%5B%22ongoing%22,%22notStarted%22%5D,%22keyword%22:%22%22,%22classify_type%22:%22recently_started%22,%22display_studio_list%22:false%7D&fields=id,name,semester_id&page=1&page_size=1000"))?;
 
         let json: Value = res.json()?;
         let Some(courses_json) = json["courses"].as_array() else {
@@ -349,7 +508,7 @@ impl Session {
                 };
                 semester_course_map
                     .entry(semester_name.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(course_data);
             }
         }
@@ -362,7 +521,7 @@ impl Session {
         semester_course_map: &HashMap<String, Vec<CourseData>>,
     ) -> Result<()> {
         std::fs::write(
-            &self.path_courses,
+            &self.paths().courses,
             serde_json::to_string(&semester_course_map).unwrap(),
         )?;
 
@@ -373,7 +532,7 @@ impl Session {
 
     /// 加载学期-课程映射表!
     pub fn load_semester_course_map(&self) -> Result<HashMap<String, Vec<CourseData>>> {
-        let data = fs::read_to_string(&self.path_courses)?;
+        let data = fs::read_to_string(&self.paths().courses)?;
         let semester_course_map: HashMap<String, Vec<CourseData>> = serde_json::from_str(&data)?;
 
         Ok(semester_course_map)
@@ -382,7 +541,7 @@ impl Session {
     /// 存储已选课程!
     pub fn store_selected_courses(&self, selected_courses: &Vec<CourseFull>) -> Result<()> {
         std::fs::write(
-            &self.path_selected_courses,
+            &self.paths().selected_courses,
             serde_json::to_string(selected_courses)?,
         )?;
 
@@ -393,7 +552,7 @@ impl Session {
 
     /// 加载已选课程!
     pub fn load_selected_courses(&self) -> Result<Vec<CourseFull>> {
-        let data = fs::read_to_string(&self.path_selected_courses)?;
+        let data = fs::read_to_string(&self.paths().selected_courses)?;
         let selected_courses: Vec<CourseFull> = serde_json::from_str(&data)?;
 
         #[cfg(debug_assertions)]
@@ -403,23 +562,31 @@ impl Session {
     }
 
     /// 存储已下载课件记录!
-    pub fn store_activity_upload_record(&self, activity_upload_record: &Vec<u64>) -> Result<()> {
-        std::fs::write(
-            &self.path_activity_upload_record,
-            serde_json::to_string(activity_upload_record)?,
-        )?;
+    pub fn store_activity_upload_record(
+        &self,
+        activity_upload_record: &ActivityUploadRecord,
+    ) -> Result<()> {
+        self.persist_activity_upload_record(activity_upload_record)?;
 
         success!(
             "存储已下载课件记录 -> {}",
-            &self.path_activity_upload_record.display()
+            &self.paths().activity_upload_record.display()
         );
         Ok(())
     }
 
     /// 加载已下载课件记录!
-    pub fn load_activity_upload_record(&self) -> Result<Vec<u64>> {
-        let data = fs::read_to_string(&self.path_activity_upload_record)?;
-        let activity_upload_record: Vec<u64> = serde_json::from_str(&data)?;
+    ///
+    /// 兼容旧版本只记录已下载 id 的数组格式
+    pub fn load_activity_upload_record(&self) -> Result<ActivityUploadRecord> {
+        let data = fs::read_to_string(&self.paths().activity_upload_record)?;
+        let activity_upload_record = match serde_json::from_str(&data)? {
+            ActivityUploadRecordCompat::Legacy(downloaded) => ActivityUploadRecord {
+                downloaded,
+                ..Default::default()
+            },
+            ActivityUploadRecordCompat::Current(record) => record,
+        };
 
         #[cfg(debug_assertions)]
         success!("加载已下载课件记录");
@@ -433,56 +600,44 @@ impl Session {
             "https://courses.zju.edu.cn/api/courses/{}/activities",
             course_id
         );
-        for attempt in 1..=utils::MAX_RETRIES {
-            match self.client.get(&url).send() {
-                Ok(res) => match res.json::<Value>() {
-                    Ok(json) => {
-                        if let Some(activities) = json["activities"].as_array() {
-                            #[cfg(debug_assertions)]
-                            success!("{}::activities", course_name.trim());
-                            return Ok(activities.clone());
-                        } else {
-                            println!("{:#?}", json);
-                            warning!(
-                                "retry {attempt}/{}: {course_name} 的返回 json 无 activities 字段",
-                                utils::MAX_RETRIES,
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        #[cfg(debug_assertions)]
-                        warning!(
-                            "retry {attempt}/{}: {course_name} 的返回无法解析为 json: {e}",
-                            utils::MAX_RETRIES,
-                        );
-                    }
-                },
-                Err(e) => {
-                    warning!(
-                        "retry {attempt}/{}: {course_name} 的请求失败: {e}",
-                        utils::MAX_RETRIES,
-                    );
-                }
-            }
-        }
-        Err(anyhow!("{course_name} 的请求失败"))
+        let json: Value = self.send(self.client.get(&url))?.json()?;
+        let Some(activities) = json["activities"].as_array() else {
+            #[cfg(debug_assertions)]
+            println!("{:#?}", json);
+            return Err(anyhow!("{course_name} 的返回 json 无 activities 字段"));
+        };
+        #[cfg(debug_assertions)]
+        success!("{}::activities", course_name.trim());
+        Ok(activities.clone())
+    }
+
+    /// 获取课程的活动列表，以及各活动下的文件
+    pub fn get_course_activities(
+        &self,
+        course_id: u64,
+        course_name: &str,
+    ) -> Result<Vec<Activity>> {
+        let activities = self.fetch_activities(course_id, course_name)?;
+        Ok(activities.iter().filter_map(Activity::from_json).collect())
     }
 
     /// 拉取下载任务！
-    fn fetch_download_tasks(
+    ///
+    /// 返回新的下载任务，以及因设置跳过的文件数
+    pub fn fetch_download_tasks(
         &self,
         selected_courses: Vec<CourseFull>,
-        activity_upload_record: &Vec<u64>,
+        activity_upload_record: &ActivityUploadRecord,
         settings: &utils::Settings,
-    ) -> Result<Vec<(String, String, u64, String)>> {
+    ) -> Result<(Vec<DownloadTask>, usize)> {
         #[cfg(debug_assertions)]
         let start = std::time::Instant::now();
 
         let num = selected_courses.len();
-        let pool = ThreadPoolBuilder::new().num_threads(num).build()?;
+        let pool = self.api_pool(num)?;
 
         // 使用线程池执行并行操作
-        let tasks: Vec<(String, String, u64, String)> = pool.install(|| {
+        let (tasks, skipped) = pool.install(|| {
             selected_courses
                 .par_iter()
                 .filter_map(|selected_course| {
@@ -496,115 +651,112 @@ impl Session {
                             }
                         };
 
-                    let local_tasks: Vec<(String, String, u64, String)> = activities
+                    let mut skipped = 0;
+                    let local_tasks: Vec<DownloadTask> = activities
                         .iter()
-                        .filter_map(|activity| activity["uploads"].as_array())
-                        .flat_map(|uploads| uploads.iter())
-                        .filter_map(|upload| {
-                            // 提取 reference_id，如果不存在则跳过
-                            let id = upload["reference_id"].as_u64()?;
-                            if activity_upload_record.contains(&id) {
-                                return None;
-                            }
-
-                            // 提取文件名，如果不存在则使用默认值
-                            let name = upload["name"].as_str()?.to_string();
-
-                            // 根据设置决定是否跳过 mp4 文件
-                            if settings.mp4_trashed
-                                && PathBuf::from(&name)
-                                    .extension()
-                                    .and_then(|ext| ext.to_str())
-                                    .unwrap_or("")
-                                    .to_lowercase()
-                                    == "mp4"
-                            {
-                                return None;
-                            }
-
-                            // 构建任务元组
-                            Some((
-                                selected_course.semester.clone(),
-                                selected_course.name.clone(),
-                                id,
-                                name,
-                            ))
+                        .flat_map(|activity| {
+                            activity_download_tasks(
+                                selected_course,
+                                activity,
+                                activity_upload_record,
+                                settings,
+                                &mut skipped,
+                            )
                         })
                         .collect();
-                    // 如果没有任务，则返回 None，否则返回任务列表
-                    if local_tasks.is_empty() {
-                        None
-                    } else {
-                        Some(local_tasks)
-                    }
+                    Some((local_tasks, skipped))
                 })
-                .flatten()
-                .collect()
+                .reduce(
+                    || (Vec::new(), 0),
+                    |(mut tasks, skipped), (local_tasks, local_skipped)| {
+                        tasks.extend(local_tasks);
+                        (tasks, skipped + local_skipped)
+                    },
+                )
         });
 
         #[cfg(debug_assertions)]
         println!("fetch_activities: {:?}", start.elapsed());
 
-        Ok(tasks)
+        Ok((tasks, skipped))
     }
 
-    /// 拉取新课件！
-    pub fn fetch_activity_uploads(
+    /// 静默写入已下载课件记录，写入过程是原子的
+    fn persist_activity_upload_record(
         &self,
-        selected_courses: Vec<CourseFull>,
-        mut activity_upload_record: Vec<u64>,
-        settings: &utils::Settings,
+        activity_upload_record: &ActivityUploadRecord,
     ) -> Result<()> {
-        begin!("更新课件信息");
-        let tasks =
-            self.fetch_download_tasks(selected_courses, &activity_upload_record, settings)?;
-
-        if tasks.is_empty() {
-            warning!("没有新课件");
-            return Ok(());
-        }
-        end!("更新课件信息");
+        utils::atomic_write(
+            &self.paths().activity_upload_record,
+            serde_json::to_string(activity_upload_record)?,
+        )
+    }
 
+    /// 下载课件任务，并将结果写入记录！
+    ///
+    /// 成功的任务记为已下载，失败的任务连同原因记入失败队列，供 fetch --retry-failed 重试。
+    /// 每个任务完成后立即写入记录；Ctrl+C 会取消未完成的任务，被取消的任务不写入记录
+    pub fn fetch_activity_uploads(
+        &self,
+        tasks: Vec<DownloadTask>,
+        activity_upload_record: ActivityUploadRecord,
+        settings: &utils::Settings,
+    ) -> Result<FetchSummary> {
+        let _guard = utils::DownloadGuard::new();
         waiting!("拉取新课件");
         let multi_pb = Arc::new(MultiProgress::new());
-        // 进度条样式
-        let pb_style = ProgressStyle::with_template(
-            "{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
-        )
-        .unwrap()
-        .progress_chars("=>-");
+        let pb_style = download_progress_style();
+        let record = Mutex::new((activity_upload_record, FetchSummary::default()));
 
-        // 用自定义线程池将并发限制为 4
-        let pool = ThreadPoolBuilder::new().num_threads(4).build()?;
+        // 用自定义线程池限制同时下载的课件数
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(settings.max_parallel_downloads.max(1))
+            .build()?;
         pool.install(|| {
-            let successful_uploads: Vec<u64> = tasks
-                .par_iter()
-                .filter_map(|(semester, course_name, upload_id, file_name)| {
+            tasks.par_iter().for_each(|task| {
+                let result = if utils::is_cancelled() {
+                    Err(anyhow!("已取消"))
+                } else {
                     let pb = multi_pb.add(ProgressBar::new(0));
                     pb.set_style(pb_style.clone());
-                    pb.set_message(format!("\x1b[34m⚙\x1b[0m {}", file_name));
-                    if let Err(e) = self.download_upload(
-                        &settings.storage_dir.join(semester).join(course_name),
-                        *upload_id,
-                        file_name,
-                        settings.is_pdf,
-                        pb,
-                    ) {
-                        error!("下载 {file_name} ：{e}");
-                        return None;
+                    pb.set_message(format!("\x1b[34m⚙\x1b[0m {}", task.name));
+                    self.download_task(task, settings, pb)
+                };
+
+                let mut guard = record.lock().unwrap();
+                let (activity_upload_record, summary) = &mut *guard;
+                match result {
+                    Ok(bytes) => {
+                        summary.succeeded += 1;
+                        summary.succeeded_bytes += bytes;
+                        activity_upload_record.record_downloaded(task);
                     }
-                    Some(*upload_id)
-                })
-                .collect();
-            if !successful_uploads.is_empty() {
-                activity_upload_record.extend(successful_uploads);
-                if let Err(e) = self.store_activity_upload_record(&activity_upload_record) {
+                    Err(_) if utils::is_cancelled() => {
+                        summary.cancelled += 1;
+                        return;
+                    }
+                    Err(e) => {
+                        error!("下载 {} ：{e}", task.name);
+                        summary.failed += 1;
+                        summary.failed_bytes += task.size;
+                        activity_upload_record.record_failed(task, e.to_string());
+                    }
+                }
+                if let Err(e) = self.persist_activity_upload_record(activity_upload_record) {
                     error!("存储下载课件记录：{e}");
                 }
-            }
+            })
         });
 
-        Ok(())
+        let (activity_upload_record, summary) = record.into_inner().unwrap();
+        if summary.cancelled > 0 {
+            warning!("已取消 {} 个未完成的课件", summary.cancelled);
+        }
+        if let Err(e) = self.store_activity_upload_record(&activity_upload_record) {
+            error!("存储下载课件记录：{e}");
+        }
+
+        Ok(summary)
     }
 
     /// 下载一个upload文件！
@@ -615,13 +767,13 @@ impl Session {
         name: &str,
         is_pdf: bool,
         pb: ProgressBar,
-    ) -> Result<()> {
+    ) -> Result<u64> {
         let download_url = if is_pdf {
             let mut retries = 0;
             loop {
-                let json:Value = self.get(format!("https://courses.zju.edu.cn/api/uploads/reference/document/{id}/url?preview=true")).send()?.json().or_else(|e| {
+                let json:Value = self.send(self.get(format!("https://courses.zju.edu.cn/api/uploads/reference/document/{id}/url?preview=true")))?.json().map_err(|e| {
                         error!("json失败：{e}");
-                        Err(e)
+                        e
                     })?;
 
                 let Some(status) = json["status"].as_str() else {
@@ -635,19 +787,16 @@ impl Session {
                 }
 
                 retries += 1;
-                if retries == utils::MAX_RETRIES {
-                    error!("雪灾浙大一直准备不好 {name}");
-                    return Ok(());
+                if retries >= self.retry.attempts() {
+                    return Err(anyhow!("雪灾浙大一直准备不好 {name}"));
                 }
+                // 等待服务器转换 pdf
+                std::thread::sleep(self.retry.delay(retries));
             }
         } else {
             format!("https://courses.zju.edu.cn/api/uploads/reference/{id}/blob")
         };
 
-        let mut res = self.get(&download_url).send()?;
-
-        fs::create_dir_all(path_download)?;
-
         // 修改文件名的拓展名与下载链接一致
         let file_name = if is_pdf {
             let extension = std::path::Path::new(&download_url)
@@ -663,7 +812,25 @@ impl Session {
             name.to_string()
         };
 
-        let mut file = File::create(std::path::Path::new(path_download).join(&file_name))?;
+        self.download_file(&download_url, path_download, &file_name, pb)
+    }
+
+    /// 将链接内容下载为 path_download 下的 file_name 文件，返回下载的字节数
+    fn download_file(
+        &self,
+        download_url: &str,
+        path_download: &PathBuf,
+        file_name: &str,
+        pb: ProgressBar,
+    ) -> Result<u64> {
+        let mut res = self.send(self.get(download_url))?;
+
+        fs::create_dir_all(path_download)?;
+
+        // 先下载到 .part 文件，完成后再重命名，取消时删除
+        let path_file = path_download.join(file_name);
+        let path_part = path_download.join(format!("{file_name}.part"));
+        let mut file = File::create(&path_part)?;
 
         let total_size = res
             .headers()
@@ -678,18 +845,68 @@ impl Session {
         }
 
         let mut buffer = [0; 8192];
+        let mut downloaded: u64 = 0;
 
-        loop {
-            let bytes = res.read(&mut buffer)?;
-            if bytes == 0 {
-                break;
+        let result = loop {
+            if utils::is_cancelled() {
+                pb.abandon_with_message(format!("\x1b[33m!\x1b[0m {file_name} 已取消"));
+                break Err(anyhow!("已取消"));
+            }
+            let bytes = match res.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(bytes) => bytes,
+                Err(e) => break Err(e.into()),
+            };
+            if let Err(e) = file.write_all(&buffer[..bytes]) {
+                break Err(e.into());
             }
-            file.write_all(&buffer[..bytes])?;
             pb.inc(bytes as u64);
+            downloaded += bytes as u64;
+            self.throttle.consume_bandwidth(bytes as u64);
+        };
+        drop(file);
+        if let Err(e) = result {
+            let _ = fs::remove_file(&path_part);
+            return Err(e);
         }
+        fs::rename(&path_part, &path_file)?;
 
         pb.finish_with_message(format!("\x1b[32m✓\x1b[0m {file_name}"));
-        Ok(())
+        Ok(downloaded)
+    }
+
+    /// 执行一个下载任务，返回写入的字节数！
+    ///
+    /// 文件下载为原文件，页面保存为 Markdown，链接保存为快捷方式，视频下载视频源
+    pub fn download_task(
+        &self,
+        task: &DownloadTask,
+        settings: &utils::Settings,
+        pb: ProgressBar,
+    ) -> Result<u64> {
+        let path_download = settings
+            .storage_dir
+            .join(&task.semester)
+            .join(&task.course_name);
+        match &task.kind {
+            TaskKind::Upload => {
+                self.download_upload(&path_download, task.id, &task.name, settings.is_pdf, pb)
+            }
+            TaskKind::Page { content } => {
+                fs::create_dir_all(&path_download)?;
+                fs::write(path_download.join(&task.name), content)?;
+                pb.finish_with_message(format!("\x1b[32m✓\x1b[0m {}", task.name));
+                Ok(content.len() as u64)
+            }
+            TaskKind::Link { url } => {
+                let shortcut = link_shortcut(&task.name, url);
+                fs::create_dir_all(&path_download)?;
+                fs::write(path_download.join(&task.name), &shortcut)?;
+                pb.finish_with_message(format!("\x1b[32m✓\x1b[0m {}", task.name));
+                Ok(shortcut.len() as u64)
+            }
+            TaskKind::Video { url } => self.download_file(url, &path_download, &task.name, pb),
+        }
     }
 
     /// 上传文件到个人资料库
@@ -714,38 +931,20 @@ impl Session {
         #[cfg(debug_assertions)]
         process!("已准备好发送上传请求");
 
-        let mut res;
-        let mut json: Option<Value> = None; // 使用 Option 包装
-
-        for attempt in 1..=utils::MAX_RETRIES {
-            res = self.client.post(POST_URL).json(&payload).send()?;
-            let content = res.text()?;
-            match serde_json::from_str::<Value>(&content) {
-                Ok(json_unjudged) => {
-                    #[cfg(debug_assertions)]
-                    println!("POST response as JSON: {:#?}", json_unjudged);
-                    if json_unjudged["errors"].is_object() {
-                        let err = &json_unjudged["errors"];
-                        return Err(anyhow!("上传文件出错：{err}"));
-                    }
-                    json = Some(json_unjudged);
-                    break;
-                }
-                Err(_) => {
-                    #[cfg(debug_assertions)]
-                    warning!("POST attempt {attempt}/{} Failed", utils::MAX_RETRIES);
-                }
-            }
-            #[cfg(debug_assertions)]
-            warning!("retry {attempt}/{}: 上传请求失败", utils::MAX_RETRIES);
+        let res = self.send(self.client.post(POST_URL).json(&payload))?;
+        let Ok(json) = serde_json::from_str::<Value>(&res.text()?) else {
+            return Err(anyhow!("上传请求失败"));
+        };
+        #[cfg(debug_assertions)]
+        println!("POST response as JSON: {:#?}", json);
+        if json["errors"].is_object() {
+            let err = &json["errors"];
+            return Err(anyhow!("上传文件出错：{err}"));
         }
 
         #[cfg(debug_assertions)]
         process!("上传请求已被接受");
 
-        let Some(json) = json else {
-            return Err(anyhow!("上传请求失败"));
-        };
         let Some(upload_url) = json["upload_url"].as_str() else {
             return Err(anyhow!("上传请求返回无 upload_url 字段"));
         };
@@ -765,7 +964,7 @@ impl Session {
             .mime_str("application/octet-stream")?;
         let form = multipart::Form::new().part("file", file_part);
 
-        let res = self.client.put(upload_url).multipart(form).send()?;
+        let res = self.send(self.client.put(upload_url).multipart(form))?;
 
         if !res.status().is_success() {
             let status = res.status();
@@ -791,12 +990,11 @@ impl Session {
     /// 将 学期 -> 课程 映射表转换为活跃课程列表
     pub fn filter_active_courses(
         semester_course_map: &HashMap<String, Vec<CourseData>>,
-        filtered_semester_list: &Vec<String>,
+        filtered_semester_list: &[String],
     ) -> Vec<CourseData> {
         let courses: Vec<CourseData> = filtered_semester_list
             .iter()
-            .map(|semester| semester_course_map.get(semester).unwrap().clone())
-            .flatten()
+            .flat_map(|semester| semester_course_map.get(semester).unwrap().clone())
             .collect();
 
         courses
@@ -804,7 +1002,7 @@ impl Session {
 
     /// 加载活跃课程
     pub fn load_active_courses(&self) -> Result<Vec<CourseData>> {
-        let data = fs::read_to_string(&self.path_active_courses)?;
+        let data = fs::read_to_string(&self.paths().active_courses)?;
         let active_courses: Vec<CourseData> = serde_json::from_str(&data)?;
 
         #[cfg(debug_assertions)]
@@ -816,7 +1014,7 @@ impl Session {
     /// 存储活跃课程
     pub fn store_active_courses(&self, active_courses: &Vec<CourseData>) -> Result<()> {
         fs::write(
-            &self.path_active_courses,
+            &self.paths().active_courses,
             serde_json::to_string(active_courses)?,
         )?;
 
@@ -828,7 +1026,7 @@ impl Session {
 
     /// 加载活跃学期
     pub fn load_active_semesters(&self) -> Result<Vec<String>> {
-        let data = fs::read_to_string(&self.path_active_semesters)?;
+        let data = fs::read_to_string(&self.paths().active_semesters)?;
         let active_semesters: Vec<String> = serde_json::from_str(&data)?;
 
         #[cfg(debug_assertions)]
@@ -840,7 +1038,7 @@ impl Session {
     /// 存储活跃学期
     pub fn store_active_semesters(&self, active_semesters: &Vec<String>) -> Result<()> {
         fs::write(
-            &self.path_active_semesters,
+            &self.paths().active_semesters,
             serde_json::to_string(active_semesters)?,
         )?;
 
@@ -856,68 +1054,50 @@ impl Session {
     pub fn get_homework_list(&self) -> Result<Vec<Homework>> {
         let courses = try_or_throw!(self.load_active_courses(), "加载活跃课程");
         let num = courses.len();
-        let pool = ThreadPoolBuilder::new().num_threads(num).build()?;
+        let pool = self.api_pool(num)?;
         let all_homeworks :Vec<Homework> = pool.install(||{
             courses.par_iter().filter_map(|course|{
                 let url = format!("https://courses.zju.edu.cn/api/courses/{}/homework-activities?page=1&page_size=100&reloadPage=false",course.id);
-                let mut homeworks:Vec<Homework> =Vec::new();
-                for attempt in 1..=utils::MAX_RETRIES{
-                    #[cfg(debug_assertions)]
-                    let start = std::time::Instant::now();
-                    let session = self.clone();
-                    match session.client.get(&url).send(){
-                        Err(e) => {
-                            warning!(
-                                "retry {attempt}/{}: {} 的请求失败: {e}",
-                                utils::MAX_RETRIES,
-                                course.name
-                            );
-                        },
-                        Ok(res)=> {
-                            match res.json::<Value>(){
-                                Err(e)=>{
-                                    #[cfg(debug_assertions)]
-                                    warning!(
-                                        "retry {attempt}/{}: {} 的返回无法解析为 json: {e}",
-                                        utils::MAX_RETRIES,
-                                        course.name
-                                    );
-                                },
-                                Ok(json)=>{
-                                    if let Some(homeworks_unwashed) = json["homework_activities"].as_array(){
-                                        #[cfg(debug_assertions)]
-                                        success!("{}::homeworks", course.name);
-                                        homeworks.extend(homeworks_unwashed
-                                            .iter()
-                                            .filter(|hw| hw["is_in_progress"].as_bool().unwrap())
-                                            .map(|hw| {
-                                                let description_html = hw["data"]["description"].as_str().unwrap_or("");
-                                                let description = html2text::from_read(description_html.as_bytes(), 80).unwrap();
-                                                let id = hw["id"].as_u64().unwrap();
-                                                let ddl = format_ddl(hw["deadline"].as_str().unwrap());
-                                                let status = hw["submitted"].as_bool().unwrap();
-                                                let (status_signal, ddl) = if status {
-                                                    ("\x1b[32m✓\x1b[0m", format!("\x1b[32m{ddl}\x1b[0m"))
-                                                } else {
-                                                    ("\x1b[33m!\x1b[0m", format!("\x1b[33m{ddl}\x1b[0m"))
-                                                };
-                                                let name = format!(
-                                                    "{status_signal} {}::{}\n\t{ddl}\n\t{description}",
-                                                    course.name,
-                                                    hw["title"].as_str().unwrap()
-                                                );
-                                                Homework { id, name }
-                                            })
-                                            .collect::<Vec<Homework>>());
-                                        #[cfg(debug_assertions)]
-                                        println!("{}::homeworks: {:?}", course.name, start.elapsed());
-                                        break;
-                                    }
-                                },
-                            }
-                        },
+                #[cfg(debug_assertions)]
+                let start = std::time::Instant::now();
+                let json = match self.send(self.client.get(&url)).and_then(|res| res.json::<Value>()) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        warning!("{} 的请求失败: {e}", course.name);
+                        return None;
                     }
-                }
+                };
+                let homeworks: Vec<Homework> = json["homework_activities"]
+                    .as_array()?
+                    .iter()
+                    .filter(|hw| hw["is_in_progress"].as_bool().unwrap())
+                    .map(|hw| {
+                        let description_html = hw["data"]["description"].as_str().unwrap_or("");
+                        let description = html2text::from_read(description_html.as_bytes(), 80).unwrap();
+                        let id = hw["id"].as_u64().unwrap();
+                        let deadline = format_ddl(hw["deadline"].as_str().unwrap());
+                        let submitted = hw["submitted"].as_bool().unwrap();
+                        let title = hw["title"].as_str().unwrap().to_string();
+                        let name = format!(
+                            "{} {}::{}\n\t{}\n\t{description}",
+                            homework_status(submitted),
+                            course.name,
+                            title,
+                            color_deadline(&format!("ddl: {deadline}"), submitted)
+                        );
+                        Homework {
+                            id,
+                            name,
+                            course_name: course.name.clone(),
+                            title,
+                            deadline,
+                            submitted,
+                            description,
+                        }
+                    })
+                    .collect();
+                #[cfg(debug_assertions)]
+                println!("{}::homeworks: {:?}", course.name, start.elapsed());
                 if homeworks.is_empty(){
                     None
                 }else{
@@ -929,21 +1109,140 @@ impl Session {
         Ok(all_homeworks)
     }
 
-    /// 上交作业
-    pub fn handin_homework(
-        &self,
-        homework_id: u64,
-        file_id: u64,
-        mut comment: String,
-    ) -> Result<()> {
-        let handin_url =
-            format!("https://courses.zju.edu.cn/api/course/activities/{homework_id}/submissions");
-
-        if !comment.is_empty() {
-            comment = format!("<p>{comment}<br></p>");
+    /// 获取活跃课程的公告，按发布时间从新到旧排列
+    ///
+    /// 同时返回获取失败的课程名及原因，由调用方决定如何提示，以便与“没有公告”区分
+    pub fn get_announcements(&self) -> Result<(Vec<Announcement>, Vec<CourseFailure>)> {
+        let courses = try_or_throw!(self.load_active_courses(), "加载活跃课程");
+        let num = courses.len();
+        let pool = self.api_pool(num)?;
+        let fetched: Vec<_> = pool.install(|| {
+            courses
+                .par_iter()
+                .map(|course| {
+                    let url = format!(
+                        "https://courses.zju.edu.cn/api/courses/{}/bulletins",
+                        course.id
+                    );
+                    let json = match self
+                        .send(self.client.get(&url))
+                        .and_then(|res| res.json::<Value>())
+                    {
+                        Ok(json) => json,
+                        Err(e) => return Err((course.name.clone(), e.to_string())),
+                    };
+                    let Some(bulletins) = json["bulletins"].as_array() else {
+                        return Err((
+                            course.name.clone(),
+                            "返回 json 无 bulletins 字段".to_string(),
+                        ));
+                    };
+                    Ok(bulletins
+                        .iter()
+                        .filter_map(|bulletin| Announcement::from_json(&course.name, bulletin))
+                        .collect::<Vec<Announcement>>())
+                })
+                .collect()
+        });
+        let mut announcements = Vec::new();
+        let mut failures = Vec::new();
+        for result in fetched {
+            match result {
+                Ok(course_announcements) => announcements.extend(course_announcements),
+                Err(failure) => failures.push(failure),
+            }
         }
-        let payload = json!({
-            "comment":comment,
+        announcements.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok((announcements, failures))
+    }
+
+    /// 加载已读公告
+    pub fn load_read_announcements(&self) -> Result<HashSet<u64>> {
+        let data = fs::read_to_string(&self.paths().read_announcements)?;
+        let read_announcements: HashSet<u64> = serde_json::from_str(&data)?;
+
+        #[cfg(debug_assertions)]
+        success!("加载已读公告");
+
+        Ok(read_announcements)
+    }
+
+    /// 存储已读公告
+    pub fn store_read_announcements(&self, read_announcements: &HashSet<u64>) -> Result<()> {
+        fs::write(
+            &self.paths().read_announcements,
+            serde_json::to_string(read_announcements)?,
+        )?;
+
+        #[cfg(debug_assertions)]
+        success!("存储已读公告");
+
+        Ok(())
+    }
+
+    /// 加载上次查询到的成绩，从未查询过时为 None
+    pub fn load_grade_snapshot(&self) -> Result<Option<GradeSnapshot>> {
+        let path = &self.paths().grade_snapshot;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(path)?;
+        let snapshot: GradeSnapshot = serde_json::from_str(&data)?;
+
+        #[cfg(debug_assertions)]
+        success!("加载成绩快照");
+
+        Ok(Some(snapshot))
+    }
+
+    /// 存储本次查询到的成绩
+    pub fn store_grade_snapshot(&self, snapshot: &GradeSnapshot) -> Result<()> {
+        utils::atomic_write(
+            &self.paths().grade_snapshot,
+            serde_json::to_string(snapshot)?,
+        )?;
+
+        #[cfg(debug_assertions)]
+        success!("存储成绩快照");
+
+        Ok(())
+    }
+
+    /// 统计各活跃课程的未读公告数，同时返回公告获取失败的课程数
+    pub fn count_unread_announcements(&self) -> Result<(Vec<(String, usize)>, usize)> {
+        let (announcements, failures) = self.get_announcements()?;
+        let read_announcements = self.load_read_announcements()?;
+        let mut unread_counts: Vec<(String, usize)> = Vec::new();
+        for announcement in announcements
+            .iter()
+            .filter(|announcement| !read_announcements.contains(&announcement.id))
+        {
+            match unread_counts
+                .iter_mut()
+                .find(|(course_name, _)| course_name == &announcement.course_name)
+            {
+                Some((_, count)) => *count += 1,
+                None => unread_counts.push((announcement.course_name.clone(), 1)),
+            }
+        }
+        Ok((unread_counts, failures.len()))
+    }
+
+    /// 上交作业
+    pub fn handin_homework(
+        &self,
+        homework_id: u64,
+        file_id: u64,
+        mut comment: String,
+    ) -> Result<()> {
+        let handin_url =
+            format!("https://courses.zju.edu.cn/api/course/activities/{homework_id}/submissions");
+
+        if !comment.is_empty() {
+            comment = format!("<p>{comment}<br></p>");
+        }
+        let payload = json!({
+            "comment":comment,
             "is_draft":false,
             "mode":"normal",
             "other_resources":[],
@@ -954,27 +1253,14 @@ impl Session {
         #[cfg(debug_assertions)]
         process!("已准备好发送提交作业请求");
 
-        let mut res;
-        let mut json: Option<Value> = None; // 使用 Option 包装
-
-        for attempt in 1..=utils::MAX_RETRIES {
-            res = self.client.post(&handin_url).json(&payload).send()?;
-            let content = res.text()?;
-            if let Ok(json_unjudged) = serde_json::from_str::<Value>(&content) {
-                #[cfg(debug_assertions)]
-                println!("SUBMIT POST response as JSON: {:#?}", json_unjudged);
-                if json_unjudged["errors"].is_array() {
-                    return Err(anyhow!("上交作业失败"));
-                }
-                json = Some(json_unjudged);
-                break;
-            }
-            #[cfg(debug_assertions)]
-            warning!("retry {attempt}/{}: 上传请求失败", utils::MAX_RETRIES);
-        }
-
-        if json.is_none() {
+        let res = self.send(self.client.post(&handin_url).json(&payload))?;
+        let Ok(json) = serde_json::from_str::<Value>(&res.text()?) else {
             return Err(anyhow!("上传作业失败"));
+        };
+        #[cfg(debug_assertions)]
+        println!("SUBMIT POST response as JSON: {:#?}", json);
+        if json["errors"].is_array() {
+            return Err(anyhow!("上交作业失败"));
         }
         #[cfg(debug_assertions)]
         process!("上交作业请求已被接受");
@@ -983,18 +1269,23 @@ impl Session {
 
     /// 查询成绩的核心内容
     fn query_grades(&self, form: Value) -> Result<Vec<Value>> {
-        let res = try_or_throw!(self.client.post(GRADE_URL).form(&form).send(), "查询成绩");
+        let res = try_or_throw!(
+            self.send(self.client.post(GRADE_URL).form(&form)),
+            "查询成绩"
+        );
         let json: Value = res.json()?;
         let grade_json = match json["data"]["list"].as_array() {
             Some(grade_json) => grade_json.to_owned(),
             None => {
                 let again_res = try_or_throw!(
-                    self.client.get(GRADE_SERVICE_URL).send(),
+                    self.send(self.client.get(GRADE_SERVICE_URL)),
                     "连接成绩查询主页"
                 );
                 if again_res.url().query().map(|q| q.to_owned()).is_none() {
-                    let res =
-                        try_or_throw!(self.client.post(GRADE_URL).form(&form).send(), "查询成绩");
+                    let res = try_or_throw!(
+                        self.send(self.client.post(GRADE_URL).form(&form)),
+                        "查询成绩"
+                    );
                     let json: Value = res.json()?;
                     json["data"]["list"].as_array().unwrap().to_owned()
                 } else {
@@ -1006,220 +1297,226 @@ impl Session {
         Ok(grade_json)
     }
 
-    /// 获取成绩 并打印全部
-    pub fn get_grade(&self, account: &account::AccountData) -> Result<()> {
+    /// 查询并解析全部成绩
+    fn query_grade_records(&self, account: &account::AccountData) -> Result<Vec<GradeRecord>> {
         let form = json!({
             "xh":account.stuid
         });
+        Ok(GradeRecord::parse_all(&self.query_grades(form)?))
+    }
+
+    /// 查询成绩并按 view 建立均绩计算器，已按重修策略标记被替代的成绩
+    ///
+    /// report_changes 为 true 时更新成绩快照，并打印与上次查询相比的变化
+    fn prepare_grades(
+        &self,
+        account: &account::AccountData,
+        scheme: GpaScheme,
+        view: &GradeView,
+        report_changes: bool,
+    ) -> Result<(Vec<GradeRecord>, GpaCalculator)> {
         begin!("查询成绩");
-        let grade_json = self.query_grades(form)?;
+        let mut records = self.query_grade_records(account)?;
         end!("查询成绩");
 
-        let (xn_set, xq_set) =
-            try_or_throw!(self.get_active_year_and_semester(), "获取活跃学年学期");
-        let mut weight_sum = 0.0;
-        let mut credit_sum = 0.0;
-        let mut weight_sum_semester = 0.0;
-        let mut credit_sum_semester = 0.0;
-        let mut weight_sum_year = 0.0;
-        let mut credit_sum_year = 0.0;
-        let mut big_class_weight_sum = 0.0;
-        let mut big_class_credit_sum = 0.0;
-        let mut middle_class_weight_sum = 0.0;
-        let mut middle_class_credit_sum = 0.0;
-        let mut small_class_weight_sum = 0.0;
-        let mut small_class_credit_sum = 0.0;
-        let all_grade_list: Vec<Grade> = grade_json
-            .iter()
-            .filter_map(|grade_json| {
-                let obj = grade_json.as_object()?;
-                let grade = obj["cj"].as_str()?;
-                if grade == "弃修" {
-                    return None;
-                }
-                let name = obj["kcmc"].as_str()?;
-                let xq = obj["xq"].as_str()?;
-                let xn = obj["xn"].as_str()?;
-                let credit = obj["xf"].as_str()?;
-                let gpa = obj["jd"].as_f64()?;
-                let gpa_str = format_gpa_str(gpa, 1);
-                let credit_num: f64 = credit.parse().unwrap();
-                let class_type = decide_class_type(credit_num);
-                let name_str;
-                match class_type {
-                    Class::Big => {
-                        name_str = format!("\x1b[35m{name}\x1b[0m");
-                        big_class_weight_sum += gpa * credit_num;
-                        big_class_credit_sum += credit_num;
-                    }
-                    Class::Middle => {
-                        name_str = format!("\x1b[34m{name}\x1b[0m");
-                        middle_class_weight_sum += gpa * credit_num;
-                        middle_class_credit_sum += credit_num;
-                    }
-                    Class::Small => {
-                        name_str = name.to_string();
-                        small_class_weight_sum += gpa * credit_num;
-                        small_class_credit_sum += credit_num;
+        if report_changes {
+            match self.update_grade_snapshot(&records) {
+                Ok(Some((taken_at, changes))) if !changes.is_empty() => {
+                    println!("{}", gray!("与 {taken_at} 的查询结果相比："));
+                    for change in &changes {
+                        println!("{}", grade_change_line(change));
                     }
                 }
-                weight_sum += gpa * credit_num;
-                credit_sum += credit_num;
-                if xn_set.contains(xn) {
-                    weight_sum_year += gpa * credit_num;
-                    credit_sum_year += credit_num;
-                    if xq_set.contains(xq) {
-                        weight_sum_semester += gpa * credit_num;
-                        credit_sum_semester += credit_num;
-                    }
-                }
-                Some(Grade {
-                    name: name_str.to_string(),
-                    grade: grade.to_string(),
-                    credit: credit.to_string(),
-                    gpa: gpa_str.to_string(),
-                })
-            })
+                Ok(_) => {}
+                Err(e) => warning!("更新成绩快照：{e}"),
+            }
+        }
+
+        let calculator = try_or_throw!(self.gpa_calculator(view, scheme), "获取学年学期");
+        calculator.scheme.resolve_retakes(&mut records);
+        Ok((records, calculator))
+    }
+
+    /// 获取成绩，按 view 打印
+    pub fn get_grade(
+        &self,
+        account: &account::AccountData,
+        scheme: GpaScheme,
+        view: &GradeView,
+    ) -> Result<()> {
+        let (records, calculator) = self.prepare_grades(account, scheme, view, true)?;
+        print_scheme(&calculator.scheme);
+
+        match view {
+            GradeView::Summary => {
+                print_semester_summary(&records, &calculator.scheme);
+                return Ok(());
+            }
+            GradeView::Chart => {
+                print_grade_chart(&records, &calculator.scheme);
+                return Ok(());
+            }
+            _ => {}
+        }
+        let scope = view.scope();
+        let summary = calculator.summarize(&records, scope);
+        let grade_list: Vec<Grade> = records
+            .iter()
+            .filter(|record| calculator.in_scope(record, scope))
+            .map(|record| Grade::from_record(record, &calculator.scheme))
             .collect();
 
-        let avg_gpa = format_gpa_str(weight_sum / credit_sum, 2);
-        let avg_gpa_semester = format_gpa_str(weight_sum_semester / credit_sum_semester, 2);
-        let avg_gpa_year = format_gpa_str(weight_sum_year / credit_sum_year, 2);
-        let avg_gpa_big_class = format_gpa_str(big_class_weight_sum / big_class_credit_sum, 2);
-        let avg_gpa_middle_class =
-            format_gpa_str(middle_class_weight_sum / middle_class_credit_sum, 2);
-        let avg_gpa_small_class =
-            format_gpa_str(small_class_weight_sum / small_class_credit_sum, 2);
-        let table = create_table(&all_grade_list);
+        let table = create_table(&GRADE_TABLE_HEADERS, &grade_rows(&grade_list));
         println!("{table}");
-        println!("学期均绩 | {avg_gpa_semester}/{credit_sum_semester:.1}");
-        println!("学年均绩 | {avg_gpa_year}/{credit_sum_year:.1}");
-        println!("总均绩   | {avg_gpa}/{credit_sum:.1}");
-        println!("\x1b[35m大课均绩\x1b[0m | {avg_gpa_big_class}/{big_class_credit_sum:.1}");
-        println!("\x1b[34m中课均绩\x1b[0m | {avg_gpa_middle_class}/{middle_class_credit_sum:.1}");
-        println!("小课均绩 | {avg_gpa_small_class}/{small_class_credit_sum:.1}");
+        let lines = summary.lines(scope);
+        let label_width = lines
+            .iter()
+            .map(|line| display_width(&line.label))
+            .max()
+            .unwrap_or(0);
+        for line in lines {
+            let label = pad(&color_by_group(&line.label, line.group), label_width);
+            println!(
+                "{label} | {}",
+                format_average(&line.average, &calculator.scheme)
+            );
+        }
 
         Ok(())
     }
 
-    /// 获取成绩 并打印本学期
-    pub fn get_g(&self, account: &account::AccountData) -> Result<()> {
-        let form = json!({
-            "xh":account.stuid
-        });
-        begin!("查询成绩");
-        let grade_json = self.query_grades(form)?;
-        end!("查询成绩");
+    /// 获取成绩单，用于导出
+    ///
+    /// with_gpa 为 true 时附上均绩汇总与每学期汇总；view 为 Summary 时总是附上每学期汇总
+    pub fn get_transcript(
+        &self,
+        account: &account::AccountData,
+        scheme: GpaScheme,
+        view: &GradeView,
+        with_gpa: bool,
+    ) -> Result<export::Transcript> {
+        let (records, calculator) = self.prepare_grades(account, scheme, view, false)?;
+        let scope = view.scope();
+        let mut transcript = export::Transcript::new(&records, &calculator, scope);
+        if with_gpa {
+            transcript.add_summary(&calculator.summarize(&records, scope), scope);
+        }
+        if with_gpa || matches!(view, GradeView::Summary) {
+            transcript.add_semesters(&grade::semester_summaries(&records, &calculator.scheme));
+        }
+        Ok(transcript)
+    }
 
-        let (xn_set, xq_set) =
-            try_or_throw!(self.get_active_year_and_semester(), "获取活跃学年学期");
-        let mut weight_sum = 0.0;
-        let mut credit_sum = 0.0;
-        let mut weight_sum_semester = 0.0;
-        let mut credit_sum_semester = 0.0;
-        let mut weight_sum_year = 0.0;
-        let mut credit_sum_year = 0.0;
-        let mut big_class_weight_sum = 0.0;
-        let mut big_class_credit_sum = 0.0;
-        let mut middle_class_weight_sum = 0.0;
-        let mut middle_class_credit_sum = 0.0;
-        let mut small_class_weight_sum = 0.0;
-        let mut small_class_credit_sum = 0.0;
-
-        let grade_list: Vec<Grade> = grade_json
+    /// 为模拟均绩做准备：查询成绩，并找出本学期的活跃课程中尚未出分的课程名
+    pub fn prepare_simulation(
+        &self,
+        account: &account::AccountData,
+        scheme: GpaScheme,
+    ) -> Result<(Vec<GradeRecord>, GpaCalculator, Vec<String>)> {
+        let (records, calculator) =
+            self.prepare_grades(account, scheme, &GradeView::Active, false)?;
+        let graded: Vec<&str> = records
             .iter()
-            .filter_map(|grade_json| {
-                let obj = grade_json.as_object()?;
-                let grade = obj["cj"].as_str()?;
-                if grade == "弃修" {
-                    return None;
-                }
-                let xq = obj["xq"].as_str()?;
-                let xn = obj["xn"].as_str()?;
-                let gpa = obj["jd"].as_f64()?;
-                let credit = obj["xf"].as_str()?;
-                let credit_num: f64 = credit.parse().unwrap();
-                weight_sum += gpa * credit_num;
-                credit_sum += credit_num;
-                if xn_set.contains(xn) {
-                    weight_sum_year += gpa * credit_num;
-                    credit_sum_year += credit_num;
-                    if xq_set.contains(xq) {
-                        let name = obj["kcmc"].as_str()?;
-                        let gpa_str = format_gpa_str(gpa, 1);
-                        let class_type = decide_class_type(credit_num);
-                        let name_str;
-                        match class_type {
-                            Class::Big => {
-                                name_str = format!("\x1b[35m{name}\x1b[0m");
-                                big_class_weight_sum += gpa * credit_num;
-                                big_class_credit_sum += credit_num;
-                            }
-                            Class::Middle => {
-                                name_str = format!("\x1b[34m{name}\x1b[0m");
-                                middle_class_weight_sum += gpa * credit_num;
-                                middle_class_credit_sum += credit_num;
-                            }
-                            Class::Small => {
-                                name_str = name.to_string();
-                                small_class_weight_sum += gpa * credit_num;
-                                small_class_credit_sum += credit_num;
-                            }
-                        }
-                        weight_sum_semester += gpa * credit_num;
-                        credit_sum_semester += credit_num;
-                        return Some(Grade {
-                            name: name_str.to_string(),
-                            grade: grade.to_string(),
-                            credit: credit.to_string(),
-                            gpa: gpa_str.to_string(),
-                        });
-                    }
-                }
-                None
+            .filter(|record| calculator.in_term(record))
+            .map(|record| record.name.as_str())
+            .collect();
+        // 学在浙大上的课程名可能带有后缀，两边互相包含即视为同一门课
+        let pending = try_or_throw!(self.load_active_courses(), "加载活跃课程")
+            .into_iter()
+            .map(|course| course.name)
+            .filter(|name| {
+                !graded
+                    .iter()
+                    .any(|graded| name.contains(graded) || graded.contains(name.as_str()))
             })
             .collect();
-        let avg_gpa = format_gpa_str(weight_sum / credit_sum, 2);
-        let avg_gpa_semester = format_gpa_str(weight_sum_semester / credit_sum_semester, 2);
-        let avg_gpa_year = format_gpa_str(weight_sum_year / credit_sum_year, 2);
-        let avg_gpa_big_class = format_gpa_str(big_class_weight_sum / big_class_credit_sum, 2);
-        let avg_gpa_middle_class =
-            format_gpa_str(middle_class_weight_sum / middle_class_credit_sum, 2);
-        let avg_gpa_small_class =
-            format_gpa_str(small_class_weight_sum / small_class_credit_sum, 2);
-        let table = create_table(&grade_list);
-        println!("{table}");
-        println!("学期均绩     | {avg_gpa_semester}/{credit_sum_semester:.1}");
-        println!("学年均绩     | {avg_gpa_year}/{credit_sum_year:.1}");
-        println!("总均绩       | {avg_gpa}/{credit_sum:.1}");
-        println!(
-            "{} | {avg_gpa_big_class}/{big_class_credit_sum:.1}",
-            purple!("学期大课均绩")
-        );
-        println!(
-            "{} | {avg_gpa_middle_class}/{middle_class_credit_sum:.1}",
-            blue!("学期中课均绩")
-        );
-        println!("学期小课均绩 | {avg_gpa_small_class}/{small_class_credit_sum:.1}");
+        Ok((records, calculator, pending))
+    }
 
-        Ok(())
+    /// 以本次查询到的原始成绩更新快照
+    ///
+    /// 返回上次查询的时间及此后成绩的变化，从未查询过时为 None
+    fn update_grade_snapshot(
+        &self,
+        records: &[GradeRecord],
+    ) -> Result<Option<(String, Vec<GradeChange>)>> {
+        let snapshot = GradeSnapshot::new(records);
+        let changes = self
+            .load_grade_snapshot()?
+            .map(|previous| (previous.taken_at.clone(), previous.diff(&snapshot)));
+        self.store_grade_snapshot(&snapshot)?;
+        Ok(changes)
     }
-    fn get_active_year_and_semester(&self) -> Result<(HashSet<String>, HashSet<String>)> {
-        let active_semester_list = self.load_active_semesters()?;
-        let (xn_set, xq_set): (HashSet<String>, HashSet<String>) = active_semester_list
-            .iter()
-            .map(|semester| split_semester(semester))
-            .fold(
-                (HashSet::new(), HashSet::new()),
-                |(mut xn, mut xq), (a, b)| {
-                    xn.insert(a.to_owned());
-                    xq.insert(b.to_owned());
-                    (xn, xq)
-                },
-            );
-        Ok((xn_set, xq_set))
+
+    /// 按 view 建立均绩计算器，默认以当前活跃的学年学期为当前学年学期
+    fn gpa_calculator(&self, view: &GradeView, scheme: GpaScheme) -> Result<GpaCalculator> {
+        let (xn_set, xq_set, current) = match view {
+            GradeView::Semester(semester) => {
+                let (year, term) = try_split_semester(semester)
+                    .ok_or_else(|| anyhow!("无效的学期：{semester}，示例：2023-2024秋冬"))?;
+                (
+                    HashSet::from([year.to_string()]),
+                    expand_suffix(term),
+                    Some((year.to_string(), term.to_string())),
+                )
+            }
+            GradeView::Year(year) => {
+                if !is_valid_year(year) {
+                    return Err(anyhow!("无效的学年：{year}，示例：2023-2024"));
+                }
+                (HashSet::from([year.clone()]), HashSet::new(), None)
+            }
+            _ => {
+                let active_semesters = self.load_active_semesters()?;
+                let (xn_set, xq_set) = active_semesters
+                    .iter()
+                    .map(|semester| split_semester(semester))
+                    .fold(
+                        (HashSet::new(), HashSet::new()),
+                        |(mut xn, mut xq), (a, b)| {
+                            xn.insert(a.to_owned());
+                            xq.insert(b.to_owned());
+                            (xn, xq)
+                        },
+                    );
+                (xn_set, xq_set, current_semester(&active_semesters))
+            }
+        };
+        Ok(GpaCalculator::new(xn_set, xq_set, current, scheme))
     }
-    pub fn polling(&self, account: &account::AccountData) -> Result<()> {
+    /// 持续查询时与上次查询的成绩比较并更新快照
+    ///
+    /// last 为上次查询的快照，首次查询时从本地加载；之后始终与内存中的快照比较，
+    /// 读写本地快照失败时只打印原因，不影响发现成绩变化
+    fn polling_changes(
+        &self,
+        last: &mut Option<GradeSnapshot>,
+        records: &[GradeRecord],
+        stdout: &mut impl Write,
+    ) -> Vec<GradeChange> {
+        let mut log = |message: String| {
+            print!("{}\r\n", gray!("{message}"));
+            stdout.flush().unwrap();
+        };
+        if last.is_none() {
+            match self.load_grade_snapshot() {
+                Ok(snapshot) => *last = snapshot,
+                Err(e) => log(format!("加载成绩快照失败：{e}")),
+            }
+        }
+        let snapshot = GradeSnapshot::new(records);
+        let changes = last
+            .as_ref()
+            .map(|last| last.diff(&snapshot))
+            .unwrap_or_default();
+        if let Err(e) = self.store_grade_snapshot(&snapshot) {
+            log(format!("保存成绩快照失败：{e}"));
+        }
+        *last = Some(snapshot);
+        changes
+    }
+
+    pub fn polling(&self, account: &account::AccountData, scheme: GpaScheme) -> Result<()> {
         use crossterm::{
             event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
             terminal::{disable_raw_mode, enable_raw_mode},
@@ -1239,53 +1536,24 @@ impl Session {
             }
         }
         // 首次查询本学期已出成绩
-        let form = json!({ "xh": account.stuid });
-        let grade_json = self.query_grades(form.clone())?;
-        let (xn_set, xq_set) =
-            try_or_throw!(self.get_active_year_and_semester(), "获取活跃学年学期");
-        let mut known_courses: HashSet<String> = HashSet::new();
+        let mut records = self.query_grade_records(account)?;
+        let calculator = try_or_throw!(
+            self.gpa_calculator(&GradeView::Active, scheme),
+            "获取活跃学年学期"
+        );
+        // 上次查询以来的变化，包括已出成绩的修改与删除
+        let mut last_snapshot = None;
+        let changes = self.polling_changes(&mut last_snapshot, &records, &mut stdout);
+        calculator.scheme.resolve_retakes(&mut records);
         // 显示提示信息，让用户了解可通过 Ctrl+C 或 q 键退出
         raw_println("按 Ctrl + C / q / Esc 退出持续查询...", &mut stdout);
+        for change in &changes {
+            raw_println(&grade_change_line(change), &mut stdout);
+        }
         raw_println(&gray!("( 课程 | 成绩 | 绩点 | 学分 )"), &mut stdout);
-        for grade_value in grade_json.iter() {
-            let Some(obj) = grade_value.as_object() else {
-                continue;
-            };
-            let (xq, xn) = (obj["xq"].as_str().unwrap(), obj["xn"].as_str().unwrap());
-            if !xn_set.contains(xn) || !xq_set.contains(xq) {
-                continue;
-            }
-            // 忽略“弃修”成绩
-            let grade = match obj.get("cj").and_then(|v| v.as_str()) {
-                Some(s) if s != "弃修" => s,
-                _ => continue,
-            };
-
-            let name = obj.get("kcmc").and_then(|v| v.as_str()).unwrap();
-            let gpa = obj.get("jd").and_then(|v| v.as_f64()).unwrap();
-            let credit = obj.get("xf").and_then(|v| v.as_str()).unwrap();
-            let credit_num: f64 = credit.parse().unwrap();
-            let class_type = decide_class_type(credit_num);
-            let name_str;
-            match class_type {
-                Class::Big => {
-                    name_str = purple!("{name}");
-                }
-                Class::Middle => {
-                    name_str = blue!("{name}");
-                }
-                Class::Small => {
-                    name_str = name.to_string();
-                }
-            }
-            let gpa_str = format_gpa_str(gpa, 1);
+        for record in records.iter().filter(|record| calculator.in_term(record)) {
             // 直接打印格式：课程名称 | 成绩 | 绩点 | 学分
-            let width = (30 + width_shift(&name_str)) as usize;
-            raw_println(
-                &format!("{name_str:width$} | {grade} | {gpa_str} | {credit}"),
-                &mut stdout,
-            );
-            known_courses.insert(name.to_string());
+            raw_println(&polling_line(record, "", &calculator.scheme), &mut stdout);
         }
 
         const TOTAL_SLEEP_TIME: Duration = if cfg!(debug_assertions) {
@@ -1301,12 +1569,13 @@ impl Session {
             while elapsed < TOTAL_SLEEP_TIME {
                 // 使用 poll 检查按键事件，超时时间 SLEEP_INTERVAL
                 if event::poll(SLEEP_INTERVAL).unwrap() {
-                    if let Event::Key(KeyEvent { code, .. }) = event::read().unwrap() {
+                    if let Event::Key(KeyEvent {
+                        code, modifiers, ..
+                    }) = event::read().unwrap()
+                    {
                         match code {
                             KeyCode::Char('q') => break 'outer,
-                            KeyCode::Char('c')
-                                if KeyModifiers::CONTROL == KeyModifiers::CONTROL =>
-                            {
+                            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
                                 break 'outer
                             }
                             KeyCode::Esc => break 'outer,
@@ -1316,56 +1585,26 @@ impl Session {
                 }
                 elapsed += SLEEP_INTERVAL;
             }
-            let new_grade_json = self.query_grades(form.clone())?;
-            let mut found_new = false;
-            for grade_value in new_grade_json.iter() {
-                let Some(obj) = grade_value.as_object() else {
-                    continue;
+            let mut new_records = self.query_grade_records(account)?;
+            let changes = self.polling_changes(&mut last_snapshot, &new_records, &mut stdout);
+            calculator.scheme.resolve_retakes(&mut new_records);
+            for change in &changes {
+                // 本学期新出的成绩按表格的格式打印，其余变化打印新旧值
+                let new_record = match change {
+                    GradeChange::Added(entry) => new_records.iter().find(|record| {
+                        calculator.in_term(record)
+                            && record.name == entry.name
+                            && record.semester() == entry.semester
+                    }),
+                    _ => None,
                 };
-                let (xq, xn) = (obj["xq"].as_str().unwrap(), obj["xn"].as_str().unwrap());
-                if !xn_set.contains(xn) || !xq_set.contains(xq) {
-                    continue;
-                }
-                // 忽略“弃修”
-                let grade = match obj.get("cj").and_then(|v| v.as_str()) {
-                    Some(s) if s != "弃修" => s,
-                    _ => continue,
+                let line = match new_record {
+                    Some(record) => polling_line(record, "!", &calculator.scheme),
+                    None => grade_change_line(change),
                 };
-                let name = obj.get("kcmc").and_then(|v| v.as_str()).unwrap_or("");
-                if known_courses.contains(name) {
-                    continue;
-                }
-                found_new = true;
-                let gpa = obj.get("jd").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                let credit = obj.get("xf").and_then(|v| v.as_str()).unwrap_or("");
-                let credit_num: f64 = credit.parse().unwrap();
-                let class_type = decide_class_type(credit_num);
-                let name_str;
-                match class_type {
-                    Class::Big => {
-                        name_str = purple!("!{name}");
-                    }
-                    Class::Middle => {
-                        name_str = blue!("!{name}");
-                    }
-                    Class::Small => {
-                        name_str = format!("!{name}");
-                    }
-                }
-                let gpa_str = format_gpa_str(gpa, 1);
-                // 直接打印格式：课程名称 | 成绩 | 绩点 | 学分
-                let width = (30 + width_shift(&name_str)) as usize;
-
-                raw_println(
-                    &format!("{name_str:width$} | {grade} | {gpa_str} | {credit}"),
-                    &mut stdout,
-                );
-
-                known_courses.insert(name.to_string());
-                alert(&mut stdout);
+                raw_println(&line, &mut stdout);
             }
-            #[cfg(debug_assertions)]
-            if !found_new {
+            if !changes.is_empty() {
                 alert(&mut stdout);
             }
         }
@@ -1400,35 +1639,613 @@ pub struct CourseFull {
     pub name: String,
 }
 
+/// 课程活动
+pub struct Activity {
+    pub id: u64,
+    /// 活动类型，如 material, homework, page 等
+    pub kind: String,
+    pub title: String,
+    /// 发布时间，未知时为空
+    pub publish_time: String,
+    pub uploads: Vec<Upload>,
+}
+
+impl Activity {
+    fn from_json(activity: &Value) -> Option<Activity> {
+        let uploads = activity["uploads"]
+            .as_array()
+            .map(|uploads| {
+                uploads
+                    .iter()
+                    .filter_map(|upload| {
+                        Some(Upload {
+                            id: upload["reference_id"].as_u64()?,
+                            name: upload["name"].as_str()?.to_string(),
+                            size: upload["size"].as_u64().unwrap_or(0),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let publish_time = activity["start_time"]
+            .as_str()
+            .or_else(|| activity["created_at"].as_str())
+            .map(format_time)
+            .unwrap_or_default();
+        Some(Activity {
+            id: activity["id"].as_u64()?,
+            kind: activity["type"].as_str().unwrap_or_default().to_string(),
+            title: activity["title"].as_str().unwrap_or_default().to_string(),
+            publish_time,
+            uploads,
+        })
+    }
+
+    /// 活动类型的中文名
+    pub fn kind_name(&self) -> &str {
+        match self.kind.as_str() {
+            "material" => "资料",
+            "homework" => "作业",
+            "page" => "页面",
+            "web_link" => "链接",
+            "online_video" => "视频",
+            "lesson" => "课堂",
+            "lesson_replay" => "回放",
+            "exam" => "测试",
+            "forum" => "讨论",
+            "questionnaire" => "问卷",
+            kind => kind,
+        }
+    }
+}
+
+/// 活动中的文件
+pub struct Upload {
+    /// upload 的 reference_id
+    pub id: u64,
+    pub name: String,
+    /// 文件大小(字节)，未知时为 0
+    pub size: u64,
+}
+
+/// 会话是否已过期：被重定向到统一身份认证登录页，或返回 401
+fn is_session_expired(res: &Response) -> bool {
+    res.status() == reqwest::StatusCode::UNAUTHORIZED || cas::is_login_page(res.url())
+}
+
+/// 课件下载任务
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DownloadTask {
+    pub semester: String,
+    pub course_name: String,
+    /// 文件为 upload 的 reference_id，其余为活动 id
+    pub id: u64,
+    /// 保存的文件名
+    pub name: String,
+    /// 文件大小(字节)，未知时为 0
+    pub size: u64,
+    pub kind: TaskKind,
+}
+
+/// 下载任务的类型
+#[derive(Serialize, Deserialize, Clone)]
+pub enum TaskKind {
+    /// 活动中的文件
+    Upload,
+    /// 页面活动，保存为 Markdown
+    Page { content: String },
+    /// 链接活动，保存为快捷方式
+    Link { url: String },
+    /// 视频活动，下载视频源
+    Video { url: String },
+}
+
+/// 课件记录
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ActivityUploadRecord {
+    /// 已下载的课件
+    pub downloaded: Vec<u64>,
+    /// 用户选择忽略的课件
+    pub ignored: Vec<u64>,
+    /// 已保存的页面、链接、视频活动
+    pub saved_activities: Vec<u64>,
+    /// 用户选择忽略的页面、链接、视频活动
+    pub ignored_activities: Vec<u64>,
+    /// 下载失败的任务
+    pub failed: Vec<FailedTask>,
+}
+
+/// 下载失败的任务及原因
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FailedTask {
+    pub task: DownloadTask,
+    pub reason: String,
+}
+
+/// 一次拉取的结果统计
+#[derive(Default)]
+pub struct FetchSummary {
+    pub succeeded: usize,
+    pub succeeded_bytes: u64,
+    pub failed: usize,
+    /// 失败任务的预计大小
+    pub failed_bytes: u64,
+    /// 因 Ctrl+C 取消的任务数
+    pub cancelled: usize,
+}
+
+impl ActivityUploadRecord {
+    /// 课件是否已经处理过(已下载或已忽略)
+    pub fn contains(&self, id: u64) -> bool {
+        self.downloaded.contains(&id) || self.ignored.contains(&id)
+    }
+
+    /// 页面、链接、视频活动是否已经处理过(已保存或已忽略)
+    pub fn contains_activity(&self, id: u64) -> bool {
+        self.saved_activities.contains(&id) || self.ignored_activities.contains(&id)
+    }
+
+    /// 记录下载完成的任务，并将其移出失败队列
+    pub fn record_downloaded(&mut self, task: &DownloadTask) {
+        self.remove_failed(task);
+        match task.kind {
+            TaskKind::Upload => self.downloaded.push(task.id),
+            _ => self.saved_activities.push(task.id),
+        }
+    }
+
+    /// 记录被忽略的任务，并将其移出失败队列
+    pub fn record_ignored(&mut self, task: &DownloadTask) {
+        self.remove_failed(task);
+        match task.kind {
+            TaskKind::Upload => self.ignored.push(task.id),
+            _ => self.ignored_activities.push(task.id),
+        }
+    }
+
+    /// 记录下载失败的任务，同一任务只保留最近一次的原因
+    pub fn record_failed(&mut self, task: &DownloadTask, reason: String) {
+        self.remove_failed(task);
+        self.failed.push(FailedTask {
+            task: task.clone(),
+            reason,
+        });
+    }
+
+    fn remove_failed(&mut self, task: &DownloadTask) {
+        let is_upload = matches!(task.kind, TaskKind::Upload);
+        self.failed.retain(|failed| {
+            failed.task.id != task.id || matches!(failed.task.kind, TaskKind::Upload) != is_upload
+        });
+    }
+}
+
+/// 课件记录的文件格式，旧版本只存储已下载课件 id 的数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ActivityUploadRecordCompat {
+    Legacy(Vec<u64>),
+    Current(ActivityUploadRecord),
+}
+
+/// 获取失败的课程名及原因
+pub type CourseFailure = (String, String);
+
+/// 课程公告
+pub struct Announcement {
+    pub id: u64,
+    pub course_name: String,
+    pub title: String,
+    /// 正文，已由 html 转换为文本
+    pub content: String,
+    /// 原始的发布时间，用于排序
+    pub created_at: String,
+    /// 格式化后的发布时间
+    pub publish_time: String,
+}
+
+impl Announcement {
+    fn from_json(course_name: &str, bulletin: &Value) -> Option<Announcement> {
+        let content_html = bulletin["content"].as_str().unwrap_or_default();
+        let created_at = bulletin["created_at"].as_str().unwrap_or_default();
+        Some(Announcement {
+            id: bulletin["id"].as_u64()?,
+            course_name: course_name.to_string(),
+            title: bulletin["title"].as_str().unwrap_or_default().to_string(),
+            content: html2text::from_read(content_html.as_bytes(), 80).unwrap_or_default(),
+            created_at: created_at.to_string(),
+            publish_time: format_time(created_at),
+        })
+    }
+}
+
 pub struct Homework {
     pub id: u64,
+    /// 选择作业时展示的名称，含课程名、截止时间与说明
     pub name: String,
+    pub course_name: String,
+    pub title: String,
+    /// 截止时间，如 "01-20 23:59 2025"
+    pub deadline: String,
+    pub submitted: bool,
+    pub description: String,
+}
+
+impl Homework {
+    /// 作业列表中的一行：状态 | 课程 | 作业 | 截止时间
+    pub fn row(&self) -> Vec<String> {
+        vec![
+            homework_status(self.submitted).to_string(),
+            self.course_name.clone(),
+            self.title.clone(),
+            color_deadline(&self.deadline, self.submitted),
+        ]
+    }
+}
+
+/// 已提交为绿色的 ✓，未提交为黄色的 !
+fn homework_status(submitted: bool) -> &'static str {
+    if submitted {
+        "\x1b[32m✓\x1b[0m"
+    } else {
+        "\x1b[33m!\x1b[0m"
+    }
+}
+
+fn color_deadline(deadline: &str, submitted: bool) -> String {
+    if submitted {
+        format!("\x1b[32m{deadline}\x1b[0m")
+    } else {
+        format!("\x1b[33m{deadline}\x1b[0m")
+    }
 }
 
 pub struct Grade {
     pub name: String,
+    /// 所属课程分组的序号，打印时用于给课程名着色
+    pub group: Option<usize>,
     pub grade: String,
     pub credit: String,
     pub gpa: String,
+    /// 备注，如重修、补考、不计绩点
+    pub remarks: String,
+}
+
+impl Grade {
+    /// 表格中展示的一行，绩点按 scheme 换算
+    fn from_record(record: &GradeRecord, scheme: &GpaScheme) -> Grade {
+        Grade {
+            name: record.name.clone(),
+            group: scheme.group_of(record),
+            grade: record.grade.clone(),
+            credit: format_credit(record.credit),
+            gpa: format_point(scheme.point(record), scheme, 1),
+            remarks: record.remarks(),
+        }
+    }
+}
+
+/// 按课程分组给文字着色：第一组(默认为大课)紫色，第二组(默认为中课)蓝色
+fn color_by_group(text: &str, group: Option<usize>) -> String {
+    match group {
+        Some(0) => purple!("{text}"),
+        Some(1) => blue!("{text}"),
+        _ => text.to_string(),
+    }
+}
+
+/// 学分保留一位小数，如 4 => "4.0"
+fn format_credit(credit: f64) -> String {
+    format!("{credit:.1}")
+}
+
+/// 持续查询时打印的一行：课程名称 | 成绩 | 绩点 | 学分
+fn polling_line(record: &GradeRecord, mark: &str, scheme: &GpaScheme) -> String {
+    let name_str = color_by_group(&format!("{mark}{}", record.name), scheme.group_of(record));
+    let line = format!(
+        "{} | {} | {} | {}",
+        pad(&name_str, 30),
+        record.grade,
+        format_point(scheme.point(record), scheme, 1),
+        format_credit(record.credit)
+    );
+    match record.remarks() {
+        remarks if remarks.is_empty() => line,
+        remarks => format!("{line} {}", gray!("({remarks})")),
+    }
+}
+
+/// 成绩变化的一行，如 "~ 2024-2025秋冬 微积分：88 -> 90"
+fn grade_change_line(change: &GradeChange) -> String {
+    let describe = |entry: &SnapshotEntry| {
+        if entry.attempt.is_empty() {
+            format!("{} {}", entry.semester, entry.name)
+        } else {
+            format!("{} {}({})", entry.semester, entry.name, entry.attempt)
+        }
+    };
+    match change {
+        GradeChange::Added(entry) => green!("+ {}：{}", describe(entry), entry.grade),
+        GradeChange::Removed(entry) => red!("- {}：{}", describe(entry), entry.grade),
+        GradeChange::Changed { old, new } => {
+            let mut line = format!("~ {}：{} -> {}", describe(new), old.grade, new.grade);
+            if old.credit != new.credit {
+                line.push_str(&format!(
+                    "，学分 {} -> {}",
+                    format_credit(old.credit),
+                    format_credit(new.credit)
+                ));
+            }
+            yellow!("{line}")
+        }
+    }
+}
+
+/// 均绩/学分，如 "4.21/32.5"
+fn format_average(average: &Average, scheme: &GpaScheme) -> String {
+    format!(
+        "{}/{:.1}",
+        format_point(Some(average.value()), scheme, 2),
+        average.total_credit
+    )
+}
+
+/// 按 scheme 的满分着色的绩点，无法换算时为 "-"
+fn format_point(point: Option<f64>, scheme: &GpaScheme, precision: usize) -> String {
+    let Some(point) = point else {
+        return "-".to_string();
+    };
+    let formatted_point = format!("{point:.precision$}");
+    let max = scheme.max();
+    if max <= 0.0 {
+        return formatted_point;
+    }
+    color_gpa_str(point / max * 5.0, formatted_point)
+}
+
+/// 不是默认的五分制或只统计专业课时，提示当前的计算方案
+fn print_scheme(scheme: &GpaScheme) {
+    if scheme.scale == GpaScale::Five && !scheme.major_only {
+        return;
+    }
+    let major = if scheme.major_only {
+        "，仅专业课"
+    } else {
+        ""
+    };
+    println!("{}", gray!("绩点：{}{major}", scheme.scale.name()));
+}
+
+/// 成绩的查看方式
+pub enum GradeView {
+    /// 全部成绩
+    All,
+    /// 当前活跃学期的成绩
+    Active,
+    /// 指定学期的成绩，如 "2023-2024秋冬"
+    Semester(String),
+    /// 指定学年的成绩，如 "2023-2024"
+    Year(String),
+    /// 每学期一行的汇总
+    Summary,
+    /// 学期均绩走势与成绩分布图
+    Chart,
+}
+
+impl GradeView {
+    /// 统计范围，汇总视图统计全部成绩
+    fn scope(&self) -> Scope {
+        match self {
+            GradeView::All | GradeView::Summary | GradeView::Chart => Scope::All,
+            GradeView::Active | GradeView::Semester(_) => Scope::Term,
+            GradeView::Year(_) => Scope::Year,
+        }
+    }
+}
+
+/// 打印每学期的学分、学期均绩与累计均绩，按学期先后排列
+fn print_semester_summary(records: &[GradeRecord], scheme: &GpaScheme) {
+    let rows: Vec<Vec<String>> = grade::semester_summaries(records, scheme)
+        .into_iter()
+        .map(|summary| {
+            vec![
+                summary.semester,
+                format_credit(summary.term.total_credit),
+                format_point(Some(summary.term.value()), scheme, 2),
+                format_point(Some(summary.cumulative.value()), scheme, 2),
+            ]
+        })
+        .collect();
+    println!(
+        "{}",
+        create_table(&["学期", "学分", "学期均绩", "累计均绩"], &rows)
+    );
+}
+
+/// 打印模拟的均绩
+///
+/// 指定 target 时，先求出待求解课程使各项均绩达到 target 所需的最低分数，
+/// 再按总均绩所需的分数模拟；否则待求解的课程不计入
+pub fn print_simulation(
+    records: &[GradeRecord],
+    calculator: &GpaCalculator,
+    pending: &[grade::PendingCourse],
+    target: Option<f64>,
+) {
+    let scheme = &calculator.scheme;
+    print_scheme(scheme);
+    let current = calculator.summarize(records, Scope::Term);
+    let unknown = pending
+        .iter()
+        .filter(|course| course.grade.is_none())
+        .count();
+    let mut unknown_score = None;
+    if let Some(target) = target.filter(|_| unknown > 0) {
+        for (label, scope) in [
+            ("学期均绩", Scope::Term),
+            ("学年均绩", Scope::Year),
+            ("总均绩", Scope::All),
+        ] {
+            let required = calculator.min_required_score(records, pending, target, scope);
+            match required {
+                Some(score) => {
+                    println!("{label}达到 {target:.2}：{unknown} 门待定课程至少需要 {score} 分")
+                }
+                None => println!("{label}达到 {target:.2}：{}", gray!("满分也无法达到")),
+            }
+            if scope == Scope::All {
+                unknown_score = required;
+            }
+        }
+    }
+    let simulated = calculator.simulate(records, pending, unknown_score);
+    let rows: Vec<Vec<String>> = current
+        .lines(Scope::Term)
+        .into_iter()
+        .zip(simulated.lines(Scope::Term))
+        .filter(|(line, _)| line.group.is_none())
+        .map(|(current, simulated)| {
+            vec![
+                current.label,
+                format_average(&current.average, scheme),
+                format_average(&simulated.average, scheme),
+            ]
+        })
+        .collect();
+    println!("{}", create_table(&["项目", "当前", "模拟"], &rows));
+    match unknown_score {
+        Some(score) => println!("{}", gray!("模拟时待定课程均按 {score} 分计算")),
+        None if unknown > 0 => println!("{}", gray!("{unknown} 门待定课程未计入模拟")),
+        None => {}
+    }
+}
+
+/// 图表中横条的最大长度
+const CHART_WIDTH: usize = 30;
+
+/// 打印每学期均绩的走势与各分数段的课程数
+fn print_grade_chart(records: &[GradeRecord], scheme: &GpaScheme) {
+    let max = scheme.max();
+    // 只有合格制成绩的学期没有均绩
+    let semesters: Vec<grade::SemesterSummary> = grade::semester_summaries(records, scheme)
+        .into_iter()
+        .filter(|summary| summary.term.credit > 0.0)
+        .collect();
+    if semesters.is_empty() {
+        warning!("没有计入均绩的成绩");
+        return;
+    }
+
+    let values: Vec<f64> = semesters
+        .iter()
+        .map(|summary| summary.term.value())
+        .collect();
+    let lowest = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    println!(
+        "学期均绩走势 {} {}",
+        chart::sparkline(&values, lowest, highest),
+        gray!("({lowest:.2} ~ {highest:.2})")
+    );
+    let label_width = semesters
+        .iter()
+        .map(|summary| display_width(&summary.semester))
+        .max()
+        .unwrap_or(0);
+    for (summary, value) in semesters.iter().zip(&values) {
+        println!(
+            "{} │{} {} {}",
+            pad(&summary.semester, label_width),
+            chart::bar(*value, max, CHART_WIDTH),
+            format_point(Some(*value), scheme, 2),
+            gray!("累计 {:.2}", summary.cumulative.value())
+        );
+    }
+    println!();
+
+    // 按百分制分数统计计入均绩的课程，等级制成绩按映射表换算
+    let mut bands = [(0usize, 0.0); chart::SCORE_BANDS.len()];
+    let mut pass_fail = 0;
+    for record in records.iter().filter(|record| scheme.counts(record)) {
+        if record.is_pass_fail() {
+            pass_fail += 1;
+        } else if let Some(score) = scheme.score(record) {
+            let band = &mut bands[chart::score_band(score)];
+            band.0 += 1;
+            band.1 += record.credit;
+        }
+    }
+    let most = bands.iter().map(|&(count, _)| count).max().unwrap_or(0);
+    println!("成绩分布");
+    let label_width = chart::SCORE_BANDS
+        .iter()
+        .map(|(label, _)| display_width(label))
+        .max()
+        .unwrap_or(0);
+    for ((label, _), (count, credit)) in chart::SCORE_BANDS.iter().zip(bands) {
+        println!(
+            "{label:>label_width$} │{} {count} 门 {}",
+            chart::bar(count as f64, most as f64, CHART_WIDTH),
+            gray!("{} 学分", format_credit(credit))
+        );
+    }
+    if pass_fail > 0 {
+        println!("{}", gray!("另有 {pass_fail} 门合格制课程"));
+    }
+}
+
+const GRADE_TABLE_HEADERS: [&str; 5] = ["课程", "成绩", "绩点", "学分", "备注"];
+
+fn grade_rows(grades: &[Grade]) -> Vec<Vec<String>> {
+    grades
+        .iter()
+        .map(|grade| {
+            vec![
+                color_by_group(&grade.name, grade.group),
+                grade.grade.clone(),
+                grade.gpa.clone(),
+                grade.credit.clone(),
+                grade.remarks.clone(),
+            ]
+        })
+        .collect()
 }
 
 /// 拆分 "2024-2025春夏" => ("2024-2025", "春夏") 的辅助函数
 ///
 /// 这个函数非常脆弱，只有在 semester 的格式是 "xxxx-yyyy春夏" 的时候才能正常工作
 fn split_semester(semester: &str) -> (&str, &str) {
-    for (i, c) in semester.chars().enumerate() {
-        if "春夏秋冬短".contains(c) {
-            // i 是后缀开始位置
-            return (&semester[..i], &semester[i..]);
+    try_split_semester(semester).unwrap_or_else(|| panic!("无法拆分学期：{}", semester))
+}
+
+/// 拆分用户输入的学期，格式不对时返回 None
+fn try_split_semester(semester: &str) -> Option<(&str, &str)> {
+    let (i, _) = semester
+        .char_indices()
+        .find(|(_, c)| "春夏秋冬短".contains(*c))?;
+    // i 是后缀开始位置
+    let (year, term) = (&semester[..i], &semester[i..]);
+    is_valid_year(year).then_some((year, term))
+}
+
+/// 学年是否形如 "2024-2025"
+fn is_valid_year(year: &str) -> bool {
+    match year.split_once('-') {
+        Some((a, b)) => {
+            a.len() == 4
+                && b.len() == 4
+                && a.chars().all(|c| c.is_ascii_digit())
+                && b.chars().all(|c| c.is_ascii_digit())
         }
+        None => false,
     }
-    panic!("无法拆分学期：{}", semester);
 }
 
 /// 将「年-年前缀」解析为一个便于比较的整型，"2024-2025" => 2024
 fn parse_year_prefix(prefix: &str) -> u32 {
     let parts: Vec<&str> = prefix.split('-').collect();
-    return parts[0].parse().unwrap();
+    parts[0].parse().unwrap()
 }
 
 /// 给后缀定义自定义排序规则
@@ -1451,6 +2268,39 @@ fn suffix_order(suffix: &str) -> (u8, u8) {
     }
 }
 
+/// 活跃学期中的当前学期(学年, 学期)
+///
+/// 合称的学期(如秋冬)涵盖其余活跃学期，优先作为当前学期
+fn current_semester(active_semesters: &[String]) -> Option<(String, String)> {
+    active_semesters
+        .iter()
+        .filter_map(|semester| try_split_semester(semester))
+        .reduce(|a, b| {
+            if expand_suffix(b.1).len() > expand_suffix(a.1).len() {
+                b
+            } else {
+                a
+            }
+        })
+        .map(|(year, term)| (year.to_string(), term.to_string()))
+}
+
+/// 学期后缀实际包含的所有后缀
+///
+/// "秋冬" 学期包含秋、冬与秋冬学期的课程，即 {"秋", "冬", "秋冬"}；单个学期只包含自身
+fn expand_suffix(suffix: &str) -> HashSet<String> {
+    let (group, sub) = suffix_order(suffix);
+    // subpriority 为 1 的是两个学期的合称
+    if sub != 1 {
+        return HashSet::from([suffix.to_string()]);
+    }
+    ["春", "夏", "春夏", "秋", "冬", "秋冬"]
+        .into_iter()
+        .filter(|term| suffix_order(term).0 == group)
+        .map(String::from)
+        .collect()
+}
+
 /// 根据已有的 split_semester, parse_year_prefix, suffix_order
 /// 返回：具备“最大年前缀”和“最大后缀group”的所有项，并按subpriority降序排列。
 fn filter_latest_group(semesters: &[String], active_semester: &str) -> Vec<String> {
@@ -1476,23 +2326,30 @@ fn filter_latest_group(semesters: &[String], active_semester: &str) -> Vec<Strin
         .collect();
 
     // 5) 按 subpriority 降序排序
-    final_items.sort_by(|a, b| b.3.cmp(&a.3));
+    final_items.sort_by_key(|item| std::cmp::Reverse(item.3));
 
     // 返回原学期字符串
     final_items.into_iter().map(|(s, _, _, _)| s).collect()
 }
 
-pub fn compare_semester(a:&str, b:&str)->std::cmp::Ordering{
-    let (a_prefix, a_suffix) = split_semester(a);
-    let (b_prefix, b_suffix) = split_semester(b);
+/// 按时间先后比较两个学期，无法解析的学期视为最晚，排在最后
+pub fn compare_semester(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    let ((a_prefix, a_suffix), (b_prefix, b_suffix)) =
+        match (try_split_semester(a), try_split_semester(b)) {
+            (Some(a), Some(b)) => (a, b),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => return a.cmp(b),
+        };
 
-    let (a_year, b_year) = (parse_year_prefix(a_prefix),parse_year_prefix(b_prefix));
-    if a_year != b_year{
+    let (a_year, b_year) = (parse_year_prefix(a_prefix), parse_year_prefix(b_prefix));
+    if a_year != b_year {
         return a_year.cmp(&b_year);
     }
     let (a_group, a_sub) = suffix_order(a_suffix);
     let (b_group, b_sub) = suffix_order(b_suffix);
-    if a_group != b_group{
+    if a_group != b_group {
         return a_group.cmp(&b_group);
     }
     a_sub.cmp(&b_sub)
@@ -1502,116 +2359,196 @@ fn format_ddl(original_ddl: &str) -> String {
     use chrono::{DateTime, Utc};
     let time = DateTime::parse_from_rfc3339(original_ddl).unwrap();
     let time_utc: DateTime<Utc> = time.with_timezone(&Utc);
-    let formatted_ddl = time_utc.format("ddl: %m-%d %H:%M %Y").to_string();
+    let formatted_ddl = time_utc.format("%m-%d %H:%M %Y").to_string();
     formatted_ddl
 }
 
-fn strip_ansi_codes(s: &str) -> String {
-    let mut stripped = String::new();
-    let mut chars = s.chars();
-    while let Some(c) = chars.next() {
-        if c == '\x1b' {
-            // 开始转义序列，跳过直到 'm'
-            while let Some(c_inner) = chars.next() {
-                if c_inner == 'm' {
-                    break;
-                }
-            }
-        } else {
-            stripped.push(c);
-        }
-    }
-    stripped
+fn is_mp4(name: &str) -> bool {
+    PathBuf::from(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+        == "mp4"
 }
 
-fn is_wide_char(c: char) -> bool {
-    match c {
-        // CJK Unified Ideographs
-        '\u{4E00}'..='\u{9FFF}' | '（' | '）' => true,
-        _ => false,
+/// 将活动标题转换为合法的文件名
+fn sanitize_file_name(title: &str) -> String {
+    let name: String = title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if name.is_empty() {
+        "untitled".to_string()
+    } else {
+        name
     }
 }
 
-fn wide_char_num(c: &str) -> usize {
-    c.chars().map(|c| if is_wide_char(c) { 1 } else { 0 }).sum()
+/// 生成链接快捷方式的内容，windows 与 macos 使用 .url，linux 使用 .desktop
+fn link_shortcut(name: &str, url: &str) -> String {
+    if cfg!(target_os = "linux") {
+        let title = std::path::Path::new(name)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(name);
+        format!("[Desktop Entry]\nType=Link\nName={title}\nURL={url}\nIcon=text-html\n")
+    } else {
+        format!("[InternetShortcut]\nURL={url}\n")
+    }
 }
 
-fn width_shift(c: &str) -> isize {
-    let cjk_shift: isize = c
-        .chars()
-        .map(|c| if is_wide_char(c) { -1 } else { 0 })
-        .sum();
-    let color_shift: isize = if c.contains('\x1b') { 9 } else { 0 };
-    cjk_shift + color_shift
-}
-fn display_width(s: &str) -> usize {
-    strip_ansi_codes(s)
-        .chars()
-        .map(|c| if is_wide_char(c) { 2 } else { 1 })
-        .sum()
-}
+const LINK_SHORTCUT_EXTENSION: &str = if cfg!(target_os = "linux") {
+    "desktop"
+} else {
+    "url"
+};
 
-fn calculate_column_widths(grades: &[Grade], headers: &[&str]) -> Vec<usize> {
-    // 初始化列宽为标题的宽度
-    let mut widths: Vec<usize> = headers.iter().map(|h| display_width(h)).collect();
-
-    // 更新列宽为内容的最大宽度
-    for grade in grades {
-        let columns = [&grade.name, &grade.grade, &grade.credit, &grade.gpa];
-        for (col, width) in columns.iter().zip(widths.iter_mut()) {
-            let len = display_width(col);
-            if len > *width {
-                *width = len;
+/// 从一个活动中提取尚未处理的下载任务
+///
+/// 活动中的文件总会被提取；页面保存为 Markdown，链接保存为快捷方式，
+/// 视频活动若链接可以直接下载则下载视频源，否则保存为快捷方式。
+/// 视频与 mp4 文件一样受 mp4_trashed 设置控制，被跳过的数目累加到 skipped
+fn activity_download_tasks(
+    course: &CourseFull,
+    activity: &Value,
+    activity_upload_record: &ActivityUploadRecord,
+    settings: &utils::Settings,
+    skipped: &mut usize,
+) -> Vec<DownloadTask> {
+    let new_task = |id: u64, name: String, size: u64, kind: TaskKind| DownloadTask {
+        semester: course.semester.clone(),
+        course_name: course.name.clone(),
+        id,
+        name,
+        size,
+        kind,
+    };
+
+    let mut tasks: Vec<DownloadTask> = activity["uploads"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|upload| {
+            // 提取 reference_id，如果不存在则跳过
+            let id = upload["reference_id"].as_u64()?;
+            if activity_upload_record.contains(id) {
+                return None;
+            }
+
+            // 提取文件名，如果不存在则跳过
+            let name = upload["name"].as_str()?.to_string();
+
+            // 根据设置决定是否跳过 mp4 文件
+            if settings.mp4_trashed && is_mp4(&name) {
+                *skipped += 1;
+                return None;
+            }
+
+            let size = upload["size"].as_u64().unwrap_or(0);
+            Some(new_task(id, name, size, TaskKind::Upload))
+        })
+        .collect();
+
+    let Some(id) = activity["id"].as_u64() else {
+        return tasks;
+    };
+    if activity_upload_record.contains_activity(id) {
+        return tasks;
+    }
+    let title = sanitize_file_name(activity["title"].as_str().unwrap_or_default());
+    let data = &activity["data"];
+    let link = data["link"]
+        .as_str()
+        .or_else(|| data["url"].as_str())
+        .filter(|link| !link.is_empty());
+
+    match activity["type"].as_str().unwrap_or_default() {
+        "page" => {
+            let content_html = data["content"]
+                .as_str()
+                .or_else(|| data["description"].as_str())
+                .unwrap_or_default();
+            if let Ok(text) = html2text::from_read(content_html.as_bytes(), 80) {
+                let content = format!("# {title}\n\n{text}");
+                let size = content.len() as u64;
+                tasks.push(new_task(
+                    id,
+                    format!("{title}.md"),
+                    size,
+                    TaskKind::Page { content },
+                ));
+            }
+        }
+        "web_link" => {
+            if let Some(url) = link {
+                tasks.push(new_task(
+                    id,
+                    format!("{title}.{LINK_SHORTCUT_EXTENSION}"),
+                    0,
+                    TaskKind::Link {
+                        url: url.to_string(),
+                    },
+                ));
+            }
+        }
+        "online_video" | "lesson_replay" if settings.mp4_trashed => *skipped += 1,
+        "online_video" | "lesson_replay" => {
+            if let Some(url) = link {
+                let extension = std::path::Path::new(url.split('?').next().unwrap_or(url))
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| ext.to_lowercase());
+                let (name, kind) = match extension.as_deref() {
+                    Some(ext @ ("mp4" | "flv" | "m4v" | "mov" | "webm")) => (
+                        format!("{title}.{ext}"),
+                        TaskKind::Video {
+                            url: url.to_string(),
+                        },
+                    ),
+                    // 外部视频平台，无法直接下载，保存为快捷方式
+                    _ => (
+                        format!("{title}.{LINK_SHORTCUT_EXTENSION}"),
+                        TaskKind::Link {
+                            url: url.to_string(),
+                        },
+                    ),
+                };
+                tasks.push(new_task(id, name, 0, kind));
             }
         }
+        _ => {}
     }
-    widths
+
+    tasks
 }
 
-fn create_table(data: &[Grade]) -> String {
-    const HEADERS: [&str; 4] = ["课程", "成绩", "绩点", "学分"];
-    let column_widths = calculate_column_widths(data, &HEADERS);
-    let mut table = String::new();
-
-    // 构建分隔线
-    let total_length: usize = column_widths.iter().map(|w| 1 + w + 2).sum::<usize>() + 2;
-    let mut separator = String::with_capacity(total_length);
-    for &w in &column_widths {
-        separator.push('+');
-        separator.push_str(&"-".repeat(w + 2));
-    }
-    separator.push_str("+\n");
-
-    // 添加表头
-    table.push_str(&separator);
-    table.push('|');
-    for (header, &width) in HEADERS.iter().zip(column_widths.iter()) {
-        let total_width = width - wide_char_num(header);
-        let padded = format!(" {header:total_width$} |");
-        table.push_str(&padded);
-    }
-    table.push('\n');
-    table.push_str(&separator);
-
-    // 添加数据行
-    for grade in data {
-        table.push('|');
-        let columns = [&grade.name, &grade.grade, &grade.gpa, &grade.credit];
-        for (col, &width) in columns.iter().zip(column_widths.iter()) {
-            let total_width = (width.to_isize().unwrap() + width_shift(col))
-                .to_usize()
-                .unwrap();
-            let padded = format!(" {col:total_width$} |");
-            table.push_str(&padded);
-        }
-        table.push('\n');
-        table.push_str(&separator);
+/// 将 rfc3339 时间格式化为本地时间，无法解析时原样返回
+fn format_time(original_time: &str) -> String {
+    use chrono::{DateTime, Local};
+    match DateTime::parse_from_rfc3339(original_time) {
+        Ok(time) => time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        Err(_) => original_time.to_string(),
     }
-    table
 }
 
-fn format_gpa_str(gpa: f64, precision: usize) -> String {
-    let formatted_gpa = format!("{gpa:.precision$}");
+/// 下载进度条样式
+pub fn download_progress_style() -> ProgressStyle {
+    ProgressStyle::with_template("{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("=>-")
+}
+
+/// 按五分制绩点 gpa 给 formatted_gpa 着色
+fn color_gpa_str(gpa: f64, formatted_gpa: String) -> String {
     match gpa {
         4.5..=5.0 => format!("\x1b[32m{formatted_gpa}\x1b[0m"), // 绿色
         3.5..4.5 => format!("\x1b[36m{formatted_gpa}\x1b[0m"),  // 青色
@@ -1621,15 +2558,24 @@ fn format_gpa_str(gpa: f64, precision: usize) -> String {
     }
 }
 
-enum Class {
-    Big,
-    Middle,
-    Small,
-}
-fn decide_class_type(credit_num: f64) -> Class {
-    match credit_num {
-        3.5..=7.0 => Class::Big,
-        2.0..3.5 => Class::Middle,
-        _ => Class::Small,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn semesters(semesters: &[&str]) -> Vec<String> {
+        semesters
+            .iter()
+            .map(|semester| semester.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn current_semester_prefers_combined_term() {
+        let current =
+            current_semester(&semesters(&["2024-2025冬", "2024-2025秋冬", "2024-2025秋"]));
+        assert_eq!(current, Some(("2024-2025".to_string(), "秋冬".to_string())));
+        let current = current_semester(&semesters(&["2024-2025夏"]));
+        assert_eq!(current, Some(("2024-2025".to_string(), "夏".to_string())));
+        assert_eq!(current_semester(&[]), None);
     }
 }
@@ -0,0 +1,164 @@
+use reqwest::{Method, StatusCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 第一次重试前的基础等待时间
+const BASE_DELAY: Duration = Duration::from_millis(500);
+/// 单次等待的上限
+const MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// 统一的重试策略：指数退避 + 随机抖动
+pub struct RetryPolicy {
+    /// 最多尝试的次数(含第一次)
+    attempts: AtomicU64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: AtomicU64::new(crate::utils::MAX_RETRIES),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 设置最多尝试的次数，至少为 1
+    pub fn set_attempts(&self, attempts: u64) {
+        self.attempts.store(attempts.max(1), Ordering::Relaxed);
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// 第 attempt 次失败后应等待的时间
+    ///
+    /// 等待上限为 BASE_DELAY * 2^(attempt-1)，不超过 MAX_DELAY，实际等待在 [上限/2, 上限] 间随机
+    pub fn delay(&self, attempt: u64) -> Duration {
+        let jitter: f64 = rand::random();
+        delay_cap(attempt).mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+/// 第 attempt 次失败后等待时间的上限
+fn delay_cap(attempt: u64) -> Duration {
+    let exp = attempt.saturating_sub(1).min(16) as u32;
+    BASE_DELAY.saturating_mul(1 << exp).min(MAX_DELAY)
+}
+
+/// 请求出错时是否值得重试
+///
+/// 超时与连接失败可以重试；非幂等的 POST 只在连接失败时重试，避免重复提交
+pub fn is_retryable_error(method: &Method, e: &reqwest::Error) -> bool {
+    if e.is_connect() {
+        return true;
+    }
+    is_idempotent(method) && e.is_timeout()
+}
+
+/// 响应状态码是否值得重试
+///
+/// 429 表示请求未被处理，总是可以重试；5xx 只对幂等请求重试
+pub fn is_retryable_status(method: &Method, status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || (is_idempotent(method) && status.is_server_error())
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    method != Method::POST && method != Method::PATCH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::net::TcpListener;
+
+    /// 连接被拒绝的错误
+    fn connect_error() -> reqwest::Error {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("http://{addr}"))
+            .send()
+            .unwrap_err()
+    }
+
+    /// 连接成功但迟迟没有响应的超时错误
+    fn timeout_error() -> reqwest::Error {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        Client::builder()
+            .timeout(Duration::from_millis(100))
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("http://{addr}"))
+            .send()
+            .unwrap_err()
+    }
+
+    #[test]
+    fn delay_cap_doubles_up_to_max() {
+        assert_eq!(delay_cap(1), Duration::from_millis(500));
+        assert_eq!(delay_cap(2), Duration::from_secs(1));
+        assert_eq!(delay_cap(4), Duration::from_secs(4));
+        assert_eq!(delay_cap(5), MAX_DELAY);
+        assert_eq!(delay_cap(6), MAX_DELAY);
+        assert_eq!(delay_cap(u64::MAX), MAX_DELAY);
+        // attempt 为 0 时按第一次计
+        assert_eq!(delay_cap(0), BASE_DELAY);
+    }
+
+    #[test]
+    fn delay_is_between_half_cap_and_cap() {
+        let policy = RetryPolicy::default();
+        for attempt in [1, 3, 10] {
+            let cap = delay_cap(attempt);
+            for _ in 0..100 {
+                let delay = policy.delay(attempt);
+                assert!(delay >= cap / 2 && delay <= cap, "{delay:?} 超出 {cap:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn too_many_requests_is_always_retryable() {
+        for method in [Method::GET, Method::POST, Method::PATCH] {
+            assert!(is_retryable_status(&method, StatusCode::TOO_MANY_REQUESTS));
+        }
+    }
+
+    #[test]
+    fn server_errors_are_retried_only_for_idempotent_methods() {
+        assert!(is_retryable_status(&Method::GET, StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(
+            &Method::PUT,
+            StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(!is_retryable_status(&Method::POST, StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(
+            &Method::PATCH,
+            StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(!is_retryable_status(&Method::GET, StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(&Method::GET, StatusCode::OK));
+    }
+
+    #[test]
+    fn post_is_retried_only_on_connect_errors() {
+        let connect = connect_error();
+        assert!(connect.is_connect());
+        assert!(is_retryable_error(&Method::POST, &connect));
+        assert!(is_retryable_error(&Method::GET, &connect));
+
+        let timeout = timeout_error();
+        assert!(timeout.is_timeout());
+        assert!(!is_retryable_error(&Method::POST, &timeout));
+        assert!(is_retryable_error(&Method::GET, &timeout));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::grade::{Attempt, GradeRecord};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 某次查询到的全部成绩，用于发现成绩的新增、修改与删除
+#[derive(Serialize, Deserialize)]
+pub struct GradeSnapshot {
+    /// 查询时间，如 "2025-01-20 10:00:00"
+    pub taken_at: String,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub course_code: String,
+    pub name: String,
+    /// 学期，如 "2024-2025春夏"
+    pub semester: String,
+    /// 考试性质，正常考试为空
+    pub attempt: String,
+    pub grade: String,
+    pub credit: f64,
+}
+
+impl SnapshotEntry {
+    pub fn from_record(record: &GradeRecord) -> SnapshotEntry {
+        SnapshotEntry {
+            course_code: record.course_code.clone(),
+            name: record.name.clone(),
+            semester: record.semester(),
+            attempt: match record.attempt {
+                Attempt::Normal => "",
+                Attempt::Retake => "重修",
+                Attempt::MakeUp => "补考",
+            }
+            .to_string(),
+            grade: record.grade.clone(),
+            credit: record.credit,
+        }
+    }
+
+    /// 同一学期同一课程的同一次考试视为同一条成绩
+    fn key(&self) -> (String, String, String) {
+        let course = if self.course_code.is_empty() {
+            &self.name
+        } else {
+            &self.course_code
+        };
+        (self.semester.clone(), course.clone(), self.attempt.clone())
+    }
+}
+
+/// 两次查询之间的一处变化
+pub enum GradeChange {
+    Added(SnapshotEntry),
+    Changed {
+        old: SnapshotEntry,
+        new: SnapshotEntry,
+    },
+    Removed(SnapshotEntry),
+}
+
+impl GradeSnapshot {
+    /// 以查询到的成绩建立快照，records 应为未经重修策略处理的原始成绩
+    pub fn new(records: &[GradeRecord]) -> GradeSnapshot {
+        GradeSnapshot {
+            taken_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            entries: records.iter().map(SnapshotEntry::from_record).collect(),
+        }
+    }
+
+    /// 从 self 到 newer 的变化，按学期与课程排序
+    ///
+    /// 同一条成绩可能出现多次(如同一学期的两条同名课程)，两边相同的先相互抵消，
+    /// 剩下的按出现顺序一一对应
+    pub fn diff(&self, newer: &GradeSnapshot) -> Vec<GradeChange> {
+        let mut old = index(&self.entries);
+        let mut new = index(&newer.entries);
+        let keys: BTreeSet<_> = old.keys().chain(new.keys()).cloned().collect();
+        let mut changes = Vec::new();
+        for key in keys {
+            let mut old = old.remove(&key).unwrap_or_default();
+            let mut new = new.remove(&key).unwrap_or_default();
+            old.retain(|entry| match new.iter().position(|other| other == entry) {
+                Some(i) => {
+                    new.remove(i);
+                    false
+                }
+                None => true,
+            });
+            let (mut old, mut new) = (old.into_iter(), new.into_iter());
+            loop {
+                changes.push(match (old.next(), new.next()) {
+                    (Some(old), Some(new)) => GradeChange::Changed {
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                    (Some(old), None) => GradeChange::Removed(old.clone()),
+                    (None, Some(new)) => GradeChange::Added(new.clone()),
+                    (None, None) => break,
+                });
+            }
+        }
+        changes
+    }
+}
+
+type EntryKey = (String, String, String);
+
+/// 按成绩归类，保留重复的成绩及其出现顺序
+fn index(entries: &[SnapshotEntry]) -> BTreeMap<EntryKey, Vec<&SnapshotEntry>> {
+    let mut index: BTreeMap<EntryKey, Vec<&SnapshotEntry>> = BTreeMap::new();
+    for entry in entries {
+        index.entry(entry.key()).or_default().push(entry);
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(code: &str, semester: &str, grade: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            course_code: code.to_string(),
+            name: code.to_string(),
+            semester: semester.to_string(),
+            attempt: String::new(),
+            grade: grade.to_string(),
+            credit: 2.0,
+        }
+    }
+
+    fn snapshot(entries: Vec<SnapshotEntry>) -> GradeSnapshot {
+        GradeSnapshot {
+            taken_at: "2025-01-20 10:00:00".to_string(),
+            entries,
+        }
+    }
+
+    /// 以 (类型, 课程, 旧成绩, 新成绩) 描述变化，便于比较
+    fn describe(changes: &[GradeChange]) -> Vec<(&str, &str, &str, &str)> {
+        changes
+            .iter()
+            .map(|change| match change {
+                GradeChange::Added(new) => {
+                    ("added", new.course_code.as_str(), "", new.grade.as_str())
+                }
+                GradeChange::Changed { old, new } => (
+                    "changed",
+                    new.course_code.as_str(),
+                    old.grade.as_str(),
+                    new.grade.as_str(),
+                ),
+                GradeChange::Removed(old) => {
+                    ("removed", old.course_code.as_str(), old.grade.as_str(), "")
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_reports_added_changed_and_removed() {
+        let old = snapshot(vec![
+            entry("A", "2024-2025秋冬", "85"),
+            entry("B", "2024-2025秋冬", "90"),
+            entry("C", "2024-2025秋冬", "70"),
+        ]);
+        let new = snapshot(vec![
+            entry("A", "2024-2025秋冬", "85"),
+            entry("B", "2024-2025秋冬", "92"),
+            entry("D", "2024-2025秋冬", "88"),
+        ]);
+        assert_eq!(
+            describe(&old.diff(&new)),
+            [
+                ("changed", "B", "90", "92"),
+                ("removed", "C", "70", ""),
+                ("added", "D", "", "88"),
+            ]
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn diff_keeps_duplicate_entries() {
+        let old = snapshot(vec![entry("A", "2024-2025秋冬", "85")]);
+        let new = snapshot(vec![
+            entry("A", "2024-2025秋冬", "85"),
+            entry("A", "2024-2025秋冬", "60"),
+        ]);
+        assert_eq!(describe(&old.diff(&new)), [("added", "A", "", "60")]);
+        assert_eq!(describe(&new.diff(&old)), [("removed", "A", "60", "")]);
+
+        // 重复的成绩之一修改时只报告这一条
+        let changed = snapshot(vec![
+            entry("A", "2024-2025秋冬", "62"),
+            entry("A", "2024-2025秋冬", "85"),
+        ]);
+        assert_eq!(
+            describe(&new.diff(&changed)),
+            [("changed", "A", "60", "62")]
+        );
+    }
+}
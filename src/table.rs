@@ -0,0 +1,148 @@
+use unicode_width::UnicodeWidthStr;
+
+/// 去掉字符串中的 ANSI 转义序列，如颜色
+pub fn strip_ansi_codes(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI 序列 "\x1b[...m" 以 0x40..=0x7E 之间的字符结束
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC 序列(如设置标题、超链接)以 BEL 或 "\x1b\\" 结束
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // 其余序列如 "\x1b(B"：若干 0x20..=0x2F 的中间字符后跟一个结束字符
+            Some(c) if ('\x20'..='\x2f').contains(&c) => {
+                for c in chars.by_ref() {
+                    if !('\x20'..='\x2f').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // 两个字符的序列如 "\x1b7"
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// 字符串在终端中占的列数，忽略颜色，中日韩文字与全角标点占两列
+pub fn display_width(s: &str) -> usize {
+    strip_ansi_codes(s).width()
+}
+
+/// 在右侧补空格直到占 width 列，已经更宽时原样返回
+pub fn pad(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(s));
+    format!("{s}{}", " ".repeat(padding))
+}
+
+/// 每列的宽度，取表头与该列内容中最宽的一个
+fn column_widths(headers: &[&str], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers.iter().map(|header| display_width(header)).collect();
+    for row in rows {
+        for (cell, width) in row.iter().zip(widths.iter_mut()) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    widths
+}
+
+/// 绘制表格，rows 中每一行的列数应与 headers 一致
+pub fn create_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let widths = column_widths(headers, rows);
+
+    let mut separator = String::new();
+    for &width in &widths {
+        separator.push('+');
+        separator.push_str(&"-".repeat(width + 2));
+    }
+    separator.push_str("+\n");
+
+    let line = |cells: &mut dyn Iterator<Item = &str>| {
+        let mut line = String::from("|");
+        for (cell, &width) in cells.zip(&widths) {
+            line.push_str(&format!(" {} |", pad(cell, width)));
+        }
+        line.push('\n');
+        line
+    };
+
+    let mut table = separator.clone();
+    table.push_str(&line(&mut headers.iter().copied()));
+    table.push_str(&separator);
+    for row in rows {
+        table.push_str(&line(&mut row.iter().map(String::as_str)));
+        table.push_str(&separator);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gray, green, red};
+
+    #[test]
+    fn full_width_punctuation_takes_two_columns() {
+        assert_eq!(display_width("（必修）"), 8);
+        assert_eq!(display_width("成绩：优秀，。"), 14);
+        assert_eq!(display_width("ＡＢ"), 4);
+    }
+
+    #[test]
+    fn kana_takes_two_columns() {
+        assert_eq!(display_width("ひらがな"), 8);
+        assert_eq!(display_width("カタカナ"), 8);
+        assert_eq!(display_width("日本語 a"), 8);
+    }
+
+    #[test]
+    fn ignores_multiple_colour_codes_in_one_cell() {
+        let cell = format!("{} / {}", green!("4.0"), red!("{}", gray!("不及格")));
+        assert_eq!(strip_ansi_codes(&cell), "4.0 / 不及格");
+        assert_eq!(display_width(&cell), 12);
+        assert_eq!(display_width(&pad(&cell, 14)), 14);
+    }
+
+    #[test]
+    fn strips_non_csi_escape_sequences() {
+        // OSC 超链接，分别以 "\x1b\\" 与 BEL 结束
+        let link = "\x1b]8;;https://courses.zju.edu.cn\x1b\\课程\x1b]8;;\x1b\\";
+        assert_eq!(strip_ansi_codes(link), "课程");
+        assert_eq!(strip_ansi_codes("\x1b]0;zac\x07标题"), "标题");
+        // 字符集切换与保存光标
+        assert_eq!(strip_ansi_codes("\x1b(Babc\x1b7"), "abc");
+    }
+
+    #[test]
+    fn aligns_table_columns() {
+        let rows = vec![
+            vec!["ひらがな".to_string(), green!("优秀")],
+            vec!["a".to_string(), "（缓考）".to_string()],
+        ];
+        let table = create_table(&["课程", "成绩"], &rows);
+        let widths: Vec<usize> = table.lines().map(display_width).collect();
+        assert!(widths.iter().all(|&width| width == widths[0]), "{table}");
+        assert_eq!(widths[0], 23);
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 网络限流器
+///
+/// 限制每个主机每秒的请求数、API 请求的并发数与全局下载带宽，
+/// 避免拉取时占满宿舍网络或触发平台的反滥用规则
+pub struct Throttle {
+    /// 每个主机每秒最多的请求数，0 表示不限制
+    requests_per_second: AtomicU64,
+    /// 下载带宽上限(字节/秒)，0 表示不限制
+    bandwidth_limit: AtomicU64,
+    /// 并行 API 请求的最大数目
+    max_parallel_requests: AtomicUsize,
+    /// 每个主机下一次允许发出请求的时间
+    next_request: Mutex<HashMap<String, Instant>>,
+    /// 下一次允许继续下载的时间
+    next_transfer: Mutex<Instant>,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle {
+            requests_per_second: AtomicU64::new(0),
+            bandwidth_limit: AtomicU64::new(0),
+            max_parallel_requests: AtomicUsize::new(0),
+            next_request: Mutex::new(HashMap::new()),
+            next_transfer: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl Throttle {
+    /// 更新限流参数
+    pub fn configure(
+        &self,
+        requests_per_second: u64,
+        bandwidth_limit: u64,
+        max_parallel_requests: usize,
+    ) {
+        self.requests_per_second
+            .store(requests_per_second, Ordering::Relaxed);
+        self.bandwidth_limit
+            .store(bandwidth_limit, Ordering::Relaxed);
+        self.max_parallel_requests
+            .store(max_parallel_requests, Ordering::Relaxed);
+    }
+
+    /// 并行处理 num 个 API 请求时应使用的线程数
+    pub fn parallel_requests(&self, num: usize) -> usize {
+        match self.max_parallel_requests.load(Ordering::Relaxed) {
+            0 => num.max(1),
+            max => num.clamp(1, max),
+        }
+    }
+
+    /// 等待直到允许向 host 发出下一个请求
+    pub fn wait_for_host(&self, host: &str) {
+        std::thread::sleep(self.reserve_request(host, Instant::now()));
+    }
+
+    /// 在 now 时刻为向 host 的请求预留时间，返回需要等待的时长
+    fn reserve_request(&self, host: &str, now: Instant) -> Duration {
+        let rps = self.requests_per_second.load(Ordering::Relaxed);
+        if rps == 0 {
+            return Duration::ZERO;
+        }
+        let interval = Duration::from_secs_f64(1.0 / rps as f64);
+        let mut next_request = self.next_request.lock().unwrap();
+        let next = next_request.entry(host.to_string()).or_insert(now);
+        let slot = (*next).max(now);
+        *next = slot + interval;
+        slot - now
+    }
+
+    /// 下载了 bytes 字节后，按带宽上限等待
+    pub fn consume_bandwidth(&self, bytes: u64) {
+        std::thread::sleep(self.reserve_transfer(bytes, Instant::now()));
+    }
+
+    /// 在 now 时刻记下载了 bytes 字节，返回需要等待的时长
+    fn reserve_transfer(&self, bytes: u64, now: Instant) -> Duration {
+        let limit = self.bandwidth_limit.load(Ordering::Relaxed);
+        if limit == 0 {
+            return Duration::ZERO;
+        }
+        let cost = Duration::from_secs_f64(bytes as f64 / limit as f64);
+        let mut next_transfer = self.next_transfer.lock().unwrap();
+        let slot = (*next_transfer).max(now) + cost;
+        *next_transfer = slot;
+        slot - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(requests_per_second: u64, bandwidth_limit: u64) -> Throttle {
+        let throttle = Throttle::default();
+        throttle.configure(requests_per_second, bandwidth_limit, 0);
+        throttle
+    }
+
+    #[test]
+    fn requests_to_same_host_are_spaced() {
+        let throttle = throttle(4, 0);
+        let now = Instant::now();
+        let waits: Vec<Duration> = (0..3)
+            .map(|_| throttle.reserve_request("courses.zju.edu.cn", now))
+            .collect();
+        assert_eq!(
+            waits,
+            [
+                Duration::ZERO,
+                Duration::from_millis(250),
+                Duration::from_millis(500)
+            ]
+        );
+        // 各主机分别计算
+        assert_eq!(
+            throttle.reserve_request("zjuam.zju.edu.cn", now),
+            Duration::ZERO
+        );
+        // 空闲一段时间后不再等待
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            throttle.reserve_request("courses.zju.edu.cn", later),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn zero_request_rate_is_unlimited() {
+        let throttle = throttle(0, 0);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(
+                throttle.reserve_request("courses.zju.edu.cn", now),
+                Duration::ZERO
+            );
+        }
+    }
+
+    #[test]
+    fn transfers_wait_for_bandwidth() {
+        let throttle = throttle(0, 1000);
+        let now = Instant::now();
+        assert_eq!(
+            throttle.reserve_transfer(500, now),
+            Duration::from_millis(500)
+        );
+        assert_eq!(throttle.reserve_transfer(500, now), Duration::from_secs(1));
+        let later = now + Duration::from_secs(3);
+        assert_eq!(
+            throttle.reserve_transfer(250, later),
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn zero_bandwidth_limit_is_unlimited() {
+        let throttle = throttle(0, 0);
+        let now = Instant::now();
+        assert_eq!(throttle.reserve_transfer(u64::MAX, now), Duration::ZERO);
+    }
+
+    #[test]
+    fn parallel_requests_respect_limit() {
+        let throttle = Throttle::default();
+        assert_eq!(throttle.parallel_requests(0), 1);
+        assert_eq!(throttle.parallel_requests(20), 20);
+        throttle.configure(0, 0, 4);
+        assert_eq!(throttle.parallel_requests(20), 4);
+        assert_eq!(throttle.parallel_requests(2), 2);
+    }
+}
@@ -1,12 +1,108 @@
-use crate::success;
-use anyhow::Result;
-use std::collections::HashMap;
+use crate::{grade, success};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 pub const SELECT_PROMPT: &str = "↑/↓ 选择 | Enter 确认 | Esc 退出";
 pub const MULTISELECT_PROMPT: &str = "↑/↓ 选择 | Space 选中 | Enter 确认 | Esc 退出";
 pub const MAX_RETRIES: u64 = 3;
 
+/// 是否正在下载课件
+static DOWNLOADING: AtomicBool = AtomicBool::new(false);
+/// 下载是否已被 Ctrl+C 取消
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+type ExitHook = Box<dyn Fn() + Send + Sync>;
+/// Ctrl+C 退出前要执行的操作，如保存 cookie
+static EXIT_HOOKS: Mutex<Vec<ExitHook>> = Mutex::new(Vec::new());
+
+/// 注册 Ctrl+C 退出前要执行的操作
+pub fn on_exit(hook: impl Fn() + Send + Sync + 'static) {
+    EXIT_HOOKS.lock().unwrap().push(Box::new(hook));
+}
+
+/// 安装 Ctrl+C 处理!
+///
+/// 下载课件时 Ctrl+C 只取消下载，已完成的课件仍会写入记录；
+/// 其余时候恢复终端、执行退出前的操作后退出
+pub fn install_ctrlc_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if DOWNLOADING.load(Ordering::SeqCst) {
+            CANCELLED.store(true, Ordering::SeqCst);
+            return;
+        }
+        let _ = crossterm::terminal::disable_raw_mode();
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::cursor::Show);
+        // 其他线程持有锁时不再等待，避免无法退出
+        if let Ok(hooks) = EXIT_HOOKS.try_lock() {
+            for hook in hooks.iter() {
+                hook();
+            }
+        }
+        println!();
+        std::process::exit(130);
+    })?;
+    Ok(())
+}
+
+/// 下载期间的守卫，存在期间 Ctrl+C 会取消下载而不是退出
+pub struct DownloadGuard;
+
+impl DownloadGuard {
+    pub fn new() -> Self {
+        CANCELLED.store(false, Ordering::SeqCst);
+        DOWNLOADING.store(true, Ordering::SeqCst);
+        DownloadGuard
+    }
+}
+
+impl Default for DownloadGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for DownloadGuard {
+    fn drop(&mut self) {
+        DOWNLOADING.store(false, Ordering::SeqCst);
+        CANCELLED.store(false, Ordering::SeqCst);
+    }
+}
+
+/// 下载是否已被取消，只在守卫存在期间有效
+pub fn is_cancelled() -> bool {
+    DOWNLOADING.load(Ordering::SeqCst) && CANCELLED.load(Ordering::SeqCst)
+}
+
+/// 先写入临时文件再重命名，避免中途退出时留下写了一半的文件!
+pub fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 同 atomic_write，但在 unix 上只允许所有者读写(0600)，用于保存账号等敏感数据
+pub fn atomic_write_private(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    use std::io::Write;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&tmp)?.write_all(contents.as_ref())?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
 /// 获取配置文件路径!
 pub fn get_config_path() -> Result<PathBuf> {
     use std::env::var;
@@ -38,6 +134,28 @@ pub struct Settings {
     pub storage_dir: PathBuf,
     pub is_pdf: bool,
     pub mp4_trashed: bool,
+    /// 同时下载的课件数
+    pub max_parallel_downloads: usize,
+    /// 同时进行的 API 请求数
+    pub max_parallel_requests: usize,
+    /// 下载带宽上限(KB/s)，0 表示不限制
+    pub bandwidth_limit: u64,
+    /// 每个主机每秒最多的请求数，0 表示不限制
+    pub requests_per_second: u64,
+    /// 网络请求最多尝试的次数
+    pub max_retries: u64,
+    /// 默认的绩点计算方式
+    pub gpa_scale: grade::GpaScale,
+    /// 等级制成绩对应的百分制分数，如 优秀 -> 95
+    pub grade_mapping: BTreeMap<String, f64>,
+    /// 自定义绩点表：分数不低于 min_score 时记为 point
+    pub custom_scale: Vec<grade::ScaleStep>,
+    /// 专业课的课程代码(前缀)或课程名，用于计算专业课均绩
+    pub major_courses: Vec<String>,
+    /// 同一课程有多次成绩时计入均绩的一次
+    pub retake_policy: grade::RetakePolicy,
+    /// 分别统计均绩的课程分组，默认按学分分为大课、中课、小课
+    pub course_groups: Vec<grade::CourseGroup>,
     pub path_settings: PathBuf,
 }
 
@@ -53,6 +171,17 @@ impl Settings {
             storage_dir: PathBuf::from(""),
             is_pdf: false,
             mp4_trashed: false,
+            max_parallel_downloads: 4,
+            max_parallel_requests: 8,
+            bandwidth_limit: 0,
+            requests_per_second: 10,
+            max_retries: MAX_RETRIES,
+            gpa_scale: grade::GpaScale::default(),
+            grade_mapping: grade::default_grade_mapping(),
+            custom_scale: grade::default_custom_scale(),
+            major_courses: Vec::new(),
+            retake_policy: grade::RetakePolicy::default(),
+            course_groups: grade::default_course_groups(),
             path_settings: get_config_path().unwrap().join("settings.json"),
         }
     }
@@ -128,6 +257,91 @@ impl Settings {
         Ok(())
     }
 
+    /// 设置同时下载的课件数!
+    pub fn set_max_parallel_downloads(&mut self, max_parallel_downloads: usize) -> Result<()> {
+        self.max_parallel_downloads = max_parallel_downloads.max(1);
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        success!("同时下载的课件数：{}", self.max_parallel_downloads);
+
+        Ok(())
+    }
+
+    /// 设置同时进行的 API 请求数!
+    pub fn set_max_parallel_requests(&mut self, max_parallel_requests: usize) -> Result<()> {
+        self.max_parallel_requests = max_parallel_requests.max(1);
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        success!("同时进行的 API 请求数：{}", self.max_parallel_requests);
+
+        Ok(())
+    }
+
+    /// 设置下载带宽上限(KB/s)，0 表示不限制!
+    pub fn set_bandwidth_limit(&mut self, bandwidth_limit: u64) -> Result<()> {
+        self.bandwidth_limit = bandwidth_limit;
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        if bandwidth_limit == 0 {
+            success!("下载带宽上限：不限制");
+        } else {
+            success!("下载带宽上限：{} KB/s", bandwidth_limit);
+        }
+
+        Ok(())
+    }
+
+    /// 设置每个主机每秒最多的请求数，0 表示不限制!
+    pub fn set_requests_per_second(&mut self, requests_per_second: u64) -> Result<()> {
+        self.requests_per_second = requests_per_second;
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        if requests_per_second == 0 {
+            success!("每秒请求数上限：不限制");
+        } else {
+            success!("每秒请求数上限：{}", requests_per_second);
+        }
+
+        Ok(())
+    }
+
+    /// 设置网络请求最多尝试的次数!
+    pub fn set_max_retries(&mut self, max_retries: u64) -> Result<()> {
+        self.max_retries = max_retries.max(1);
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        success!("网络请求最多尝试次数：{}", self.max_retries);
+
+        Ok(())
+    }
+
+    /// 设置默认的绩点计算方式!
+    pub fn set_gpa_scale(&mut self, gpa_scale: grade::GpaScale) -> Result<()> {
+        self.gpa_scale = gpa_scale;
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        success!("默认绩点计算方式：{}", gpa_scale.name());
+
+        Ok(())
+    }
+
+    /// 设置重修策略!
+    pub fn set_retake_policy(&mut self, retake_policy: grade::RetakePolicy) -> Result<()> {
+        self.retake_policy = retake_policy;
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path_settings, json)?;
+
+        success!("重修成绩计入：{}", retake_policy.name());
+
+        Ok(())
+    }
+
     pub fn list(&self) -> Result<()> {
         let json = serde_json::to_string_pretty(self)?;
         println!("{}", json);
@@ -135,19 +349,52 @@ impl Settings {
     }
 }
 
+/// 检查用户名是否可用：不能为空、不能为 . 或 ..，也不能包含路径分隔符与控制字符
+pub fn check_user_name(user: &str) -> Result<()> {
+    if user.is_empty() {
+        return Err(anyhow!("用户名不能为空"));
+    }
+    if user == "." || user == ".." {
+        return Err(anyhow!("用户名不能为 {user}"));
+    }
+    if user.contains(['/', '\\']) || user.chars().any(char::is_control) {
+        return Err(anyhow!("用户名不能包含路径分隔符或控制字符"));
+    }
+    Ok(())
+}
+
+/// 配置文件路径：(accounts, settings)
+pub type ConfigPaths = (PathBuf, PathBuf);
+
+/// 每个账号独立的数据文件，位于 ~/.zac/accounts/<用户名的 hex>/ 下
+const ACCOUNT_FILES: [&str; 7] = [
+    "cookies.json",
+    "courses.json",
+    "selected_courses.json",
+    "activity_upload_record.json",
+    "active_courses.json",
+    "active_semesters.json",
+    "read_announcements.json",
+];
+
+/// 账号数据文件路径
+#[derive(Clone)]
+pub struct AccountPaths {
+    pub cookies: PathBuf,
+    pub courses: PathBuf,
+    pub selected_courses: PathBuf,
+    pub activity_upload_record: PathBuf,
+    pub active_courses: PathBuf,
+    pub active_semesters: PathBuf,
+    pub read_announcements: PathBuf,
+    /// 上次查询到的成绩，首次查询前不存在
+    pub grade_snapshot: PathBuf,
+}
+
 pub struct Config {}
 
 impl Config {
-    pub fn init() -> Result<(
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-        PathBuf,
-    )> {
+    pub fn init() -> Result<ConfigPaths> {
         let config_path = get_config_path()?;
 
         let accounts = config_path.join("accounts.json");
@@ -160,50 +407,101 @@ impl Config {
             Config::settings_init(&settings)?;
         }
 
-        let courses = config_path.join("courses.json");
-        if !courses.exists() {
-            Config::courses_init(&courses)?;
-        }
+        Ok((accounts, settings))
+    }
 
-        let selected_courses = config_path.join("selected_courses.json");
-        if !selected_courses.exists() {
-            Config::selected_courses_init(&selected_courses)?;
+    /// 初始化账号 user 的数据目录，返回其中的数据文件路径!
+    ///
+    /// 旧版本的数据文件直接位于 ~/.zac/ 下，由第一个建立数据目录的账号(即升级时的默认账号)接管
+    pub fn account_init(user: &str) -> Result<AccountPaths> {
+        let config_path = get_config_path()?;
+        let account_dir = Config::account_dir(&config_path, user)?;
+        if !account_dir.exists() {
+            // 早先的版本直接以用户名作为目录名
+            let named_dir = config_path.join("accounts").join(user);
+            if named_dir.is_dir() {
+                fs::rename(&named_dir, &account_dir)?;
+            } else {
+                fs::create_dir_all(&account_dir)?;
+                Config::migrate_legacy_files(&config_path, &account_dir)?;
+            }
         }
 
-        let activity_upload_record = config_path.join("activity_upload_record.json");
-        if !activity_upload_record.exists() {
-            Config::activity_upload_record_init(&activity_upload_record)?;
+        let paths = AccountPaths {
+            cookies: account_dir.join("cookies.json"),
+            courses: account_dir.join("courses.json"),
+            selected_courses: account_dir.join("selected_courses.json"),
+            activity_upload_record: account_dir.join("activity_upload_record.json"),
+            active_courses: account_dir.join("active_courses.json"),
+            active_semesters: account_dir.join("active_semesters.json"),
+            read_announcements: account_dir.join("read_announcements.json"),
+            grade_snapshot: account_dir.join("grade_snapshot.json"),
+        };
+
+        if !paths.courses.exists() {
+            Config::courses_init(&paths.courses)?;
         }
-
-        let cookies = config_path.join("cookies.json");
-        if !cookies.exists() {
-            Config::cookies_init(&cookies)?;
+        if !paths.selected_courses.exists() {
+            Config::selected_courses_init(&paths.selected_courses)?;
         }
+        if !paths.activity_upload_record.exists() {
+            Config::activity_upload_record_init(&paths.activity_upload_record)?;
+        }
+        if !paths.cookies.exists() {
+            Config::cookies_init(&paths.cookies)?;
+        }
+        if !paths.active_courses.exists() {
+            Config::active_courses_init(&paths.active_courses)?;
+        }
+        if !paths.active_semesters.exists() {
+            Config::active_semesters_init(&paths.active_semesters)?;
+        }
+        if !paths.read_announcements.exists() {
+            Config::read_announcements_init(&paths.read_announcements)?;
+        }
+        Ok(paths)
+    }
 
-        let active_courses = config_path.join("active_courses.json");
-        if !active_courses.exists() {
-            Config::active_courses_init(&active_courses)?;
+    /// 删除账号 user 的数据目录(cookie、课程列表、成绩快照等)
+    pub fn remove_account_data(user: &str) -> Result<()> {
+        let config_path = get_config_path()?;
+        let account_dir = Config::account_dir(&config_path, user)?;
+        // 只删除 accounts/ 下的直接子目录，不会波及其他数据
+        if account_dir.parent() != Some(config_path.join("accounts").as_path()) {
+            return Err(anyhow!("账号数据目录异常：{}", account_dir.display()));
+        }
+        if account_dir.is_dir() {
+            fs::remove_dir_all(&account_dir)?;
         }
+        Ok(())
+    }
+
+    /// 账号数据目录，目录名为用户名的 hex 编码，任何用户名都不会逃出 accounts/
+    fn account_dir(config_path: &Path, user: &str) -> Result<PathBuf> {
+        check_user_name(user)?;
+        let name: String = user.bytes().map(|b| format!("{b:02x}")).collect();
+        Ok(config_path.join("accounts").join(name))
+    }
 
-        let active_semesters = config_path.join("active_semesters.json");
-        if !active_semesters.exists() {
-            Config::active_semesters_init(&active_semesters)?;
+    /// 将旧版本位于 ~/.zac/ 下的数据文件移动到账号数据目录!
+    fn migrate_legacy_files(config_path: &Path, account_dir: &Path) -> Result<()> {
+        let mut migrated = false;
+        for name in ACCOUNT_FILES {
+            let legacy = config_path.join(name);
+            if legacy.exists() {
+                fs::rename(&legacy, account_dir.join(name))?;
+                migrated = true;
+            }
         }
-        Ok((
-            accounts,
-            settings,
-            courses,
-            selected_courses,
-            activity_upload_record,
-            cookies,
-            active_courses,
-            active_semesters,
-        ))
+        if migrated {
+            success!("迁移旧版本数据 -> {}", account_dir.display());
+        }
+        Ok(())
     }
 
     /// 初始化账号文件!
-    fn accounts_init(path_accounts: &PathBuf) -> Result<()> {
-        fs::write(path_accounts, "{}")?;
+    fn accounts_init(path_accounts: &Path) -> Result<()> {
+        atomic_write_private(path_accounts, "{}")?;
 
         success!("账号初始化文件 -> {}", path_accounts.display());
         Ok(())
@@ -239,7 +537,7 @@ impl Config {
 
     /// 初始化课件记录文件!
     fn activity_upload_record_init(path_activity_upload_record: &PathBuf) -> Result<()> {
-        fs::write(path_activity_upload_record, "[]")?;
+        fs::write(path_activity_upload_record, "{}")?;
         success!(
             "已初始化课件记录文件 -> {}",
             path_activity_upload_record.display()
@@ -264,7 +562,48 @@ impl Config {
     /// 初始化 active_semesters 文件！
     fn active_semesters_init(active_semesters: &PathBuf) -> Result<()> {
         fs::write(active_semesters, "[]")?;
-        success!("初始化 active_semesters 文件 -> {}", active_semesters.display());
+        success!(
+            "初始化 active_semesters 文件 -> {}",
+            active_semesters.display()
+        );
+        Ok(())
+    }
+
+    /// 初始化已读公告文件！
+    fn read_announcements_init(read_announcements: &PathBuf) -> Result<()> {
+        fs::write(read_announcements, "[]")?;
+        success!("初始化已读公告文件 -> {}", read_announcements.display());
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unsafe_user_names() {
+        for user in ["", ".", "..", "a/b", "..\\b", "/", "a\nb"] {
+            assert!(check_user_name(user).is_err(), "{user:?}");
+        }
+        for user in ["alice", "张三", "a.b", "...", "a b"] {
+            assert!(check_user_name(user).is_ok(), "{user:?}");
+        }
+    }
+
+    #[test]
+    fn account_dir_is_a_direct_child_of_accounts() {
+        let config_path = Path::new("/home/u/.zac");
+        let accounts = config_path.join("accounts");
+        for user in ["alice", "张三", "...", "a b"] {
+            let dir = Config::account_dir(config_path, user).unwrap();
+            assert_eq!(dir.parent(), Some(accounts.as_path()));
+        }
+        assert_eq!(
+            Config::account_dir(config_path, "ab").unwrap(),
+            accounts.join("6162")
+        );
+        assert!(Config::account_dir(config_path, "..").is_err());
+        assert!(Config::account_dir(config_path, "").is_err());
+    }
+}
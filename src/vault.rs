@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dialoguer::{theme::ColorfulTheme, Password};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// 提供主密码的环境变量，供无人值守的场景(如 polling 守护进程)使用
+pub const PASSPHRASE_ENV: &str = "ZAC_PASSPHRASE";
+
+/// 本次运行中已输入的主密码，保证每次运行只询问一次
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// 加密文件的格式
+///
+/// 密钥由主密码经 Argon2id 派生，内容以 ChaCha20-Poly1305 加密
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    version: u32,
+    /// Argon2 盐值(hex)
+    salt: String,
+    /// AEAD nonce(hex)
+    nonce: String,
+    /// 密文(hex)
+    ciphertext: String,
+}
+
+const SEALED_VERSION: u32 = 1;
+
+/// 文件内容是否为加密格式
+pub fn is_sealed(data: &str) -> bool {
+    serde_json::from_str::<Sealed>(data).is_ok()
+}
+
+/// 用主密码加密 plaintext，返回可直接写入文件的字符串
+pub fn seal(plaintext: &[u8], passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("加密失败"))?;
+    let sealed = Sealed {
+        version: SEALED_VERSION,
+        salt: to_hex(&salt),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&ciphertext),
+    };
+    Ok(serde_json::to_string(&sealed)?)
+}
+
+/// 用主密码解密 seal 得到的内容
+pub fn open(data: &str, passphrase: &str) -> Result<Vec<u8>> {
+    let sealed: Sealed = serde_json::from_str(data)?;
+    if sealed.version != SEALED_VERSION {
+        return Err(anyhow!("不支持的加密格式版本 {}", sealed.version));
+    }
+    let salt = from_hex(&sealed.salt)?;
+    let nonce = from_hex(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        return Err(anyhow!("加密文件已损坏"));
+    }
+    let ciphertext = from_hex(&sealed.ciphertext)?;
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("主密码错误或加密文件已损坏"))
+}
+
+/// 获取主密码!
+///
+/// 优先使用环境变量 ZAC_PASSPHRASE；否则询问一次并在本次运行中记住。
+/// confirm 为 true 时(首次设置主密码)要求输入两次
+pub fn passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let mut cached = PASSPHRASE.lock().unwrap();
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let theme = ColorfulTheme::default();
+    let mut prompt = Password::with_theme(&theme).with_prompt("主密码");
+    if confirm {
+        prompt = prompt.with_confirmation("确认主密码", "两次输入不一致");
+    }
+    let passphrase = prompt.interact()?;
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+/// 忘记已输入的主密码，下次重新询问
+pub fn forget_passphrase() {
+    *PASSPHRASE.lock().unwrap() = None;
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("派生密钥：{e}"))?;
+    Ok(key)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) || !hex.len().is_multiple_of(2) {
+        return Err(anyhow!("加密文件已损坏"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("加密文件已损坏")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let sealed = seal(b"{\"alice\":1}", "correct horse").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(open(&sealed, "correct horse").unwrap(), b"{\"alice\":1}");
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let sealed = seal(b"secret", "correct horse").unwrap();
+        assert!(open(&sealed, "battery staple").is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails() {
+        let sealed = seal(b"secret", "correct horse").unwrap();
+        let mut data: Sealed = serde_json::from_str(&sealed).unwrap();
+        let flipped = if data.ciphertext.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        data.ciphertext.replace_range(0..1, flipped);
+        let tampered = serde_json::to_string(&data).unwrap();
+        assert!(open(&tampered, "correct horse").is_err());
+    }
+
+    #[test]
+    fn from_hex_rejects_malformed_input() {
+        assert_eq!(from_hex("00ff10").unwrap(), [0x00, 0xff, 0x10]);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+        assert!(from_hex("+f").is_err());
+        assert!(from_hex("é0").is_err());
+    }
+}